@@ -0,0 +1,183 @@
+//! The forms 5-bit codes can be read from and written in.
+
+use std::io::{self, Write};
+
+use lorenz::ita2::{Bletchley, CharacterSet, Decoder, Encoder, Event, FromBletchley, Normalizer};
+use lorenz::modem::{self, Modem, Wav};
+use lorenz::tape::TapeStyle;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Plain text, normalised and sent through the shifts of a character set
+    Text(CharacterSet),
+    /// One code per byte
+    Raw,
+    /// The Bletchley Park alphabet, one character per code
+    Bletchley,
+    /// Two hex digits per code
+    Hex,
+    /// Punched paper tape, one row per code
+    Tape,
+    /// RTTY audio in a WAV file, shifting the given number of Hz
+    Wav(u16),
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text(CharacterSet::default())),
+            "raw" => Some(Format::Raw),
+            "bletchley" => Some(Format::Bletchley),
+            "hex" => Some(Format::Hex),
+            "tape" => Some(Format::Tape),
+            "wav" => Some(Format::Wav(170)),
+            _ => None,
+        }
+    }
+
+    /// Uses `character_set` for text, leaving the other formats alone.
+    pub fn with_character_set(self, character_set: CharacterSet) -> Format {
+        match self {
+            Format::Text(_) => Format::Text(character_set),
+            other => other,
+        }
+    }
+
+    /// Uses a `shift` of 170 or 850 Hz for audio, leaving the other formats alone.
+    pub fn with_shift(self, shift: u16) -> Format {
+        match self {
+            Format::Wav(_) => Format::Wav(shift),
+            other => other,
+        }
+    }
+
+    fn modem(shift: u16) -> Modem {
+        match shift {
+            850 => Modem::WIDE,
+            _ => Modem::NARROW,
+        }
+    }
+
+    pub fn read(self, input: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Text(set) => {
+                let text = std::str::from_utf8(input).map_err(|_| "the input is not UTF-8 text".to_string())?;
+                let text: String = Normalizer::new(text.chars(), set).collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+                Ok(Encoder::new(text.chars()).with_character_set(set).codes().collect())
+            }
+            Format::Raw => input.iter().enumerate()
+                .map(|(i, &code)| if code < 32 { Ok(code) } else { Err(format!("byte {i} is not a 5-bit code")) })
+                .collect(),
+            Format::Bletchley => {
+                let text = std::str::from_utf8(input).map_err(|_| "the input is not UTF-8 text".to_string())?;
+                FromBletchley::new(text.chars()).map(|code| code.map_err(|e| e.to_string())).collect()
+            }
+            Format::Hex => {
+                let text = std::str::from_utf8(input).map_err(|_| "the input is not hex".to_string())?;
+                text.split_whitespace()
+                    .map(|word| match u8::from_str_radix(word, 16) {
+                        Ok(code) if code < 32 => Ok(code),
+                        _ => Err(format!("{word:?} is not a 5-bit code in hex")),
+                    })
+                    .collect()
+            }
+            Format::Tape => {
+                let text = std::str::from_utf8(input).map_err(|_| "the input is not UTF-8 text".to_string())?;
+                TapeStyle::ASCII.parse(text).map(|code| code.map_err(|e| e.to_string())).collect()
+            }
+            Format::Wav(shift) => {
+                let recording = Wav::parse(input).map_err(|e| e.to_string())?;
+                Format::modem(shift)
+                    .with_sample_rate(recording.sample_rate)
+                    .map_err(|e| e.to_string())?
+                    .receive(recording.samples())
+                    .map(|code| code.map_err(|e| e.to_string()))
+                    .collect()
+            }
+        }
+    }
+
+    pub fn write(self, codes: &[u8], out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Format::Text(set) => {
+                let text: String = Decoder::new(codes.iter().copied())
+                    .with_character_set(set)
+                    .filter_map(Event::as_char)
+                    .collect();
+                out.write_all(text.as_bytes())
+            }
+            Format::Raw => out.write_all(codes),
+            Format::Bletchley => {
+                for line in codes.chunks(60) {
+                    writeln!(out, "{}", Bletchley(line))?;
+                }
+                Ok(())
+            }
+            Format::Hex => {
+                for line in codes.chunks(16) {
+                    let words: Vec<String> = line.iter().map(|code| format!("{code:02x}")).collect();
+                    writeln!(out, "{}", words.join(" "))?;
+                }
+                Ok(())
+            }
+            Format::Tape => write!(out, "{}", TapeStyle::ASCII.draw(codes)),
+            Format::Wav(shift) => {
+                let modem = Format::modem(shift);
+                let audio: Vec<i16> = modem.modulate(codes.iter().copied()).collect();
+                let samples = u32::try_from(audio.len())
+                    .map_err(|_| io::Error::other(format!("{} samples are too many for a WAV file", audio.len())))?;
+                let header = modem::header(modem.sample_rate(), samples).map_err(|e| io::Error::other(e.to_string()))?;
+                out.write_all(&header)?;
+                for sample in audio {
+                    out.write_all(&sample.to_le_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(format: Format, codes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        format.write(codes, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let codes: Vec<u8> = (0..32).chain((0..100u16).map(|i| (i * 7 % 32) as u8)).collect();
+
+        for format in [Format::Raw, Format::Bletchley, Format::Hex, Format::Tape, Format::Wav(170), Format::Wav(850)] {
+            assert_eq!(format.read(&written(format, &codes)), Ok(codes.clone()), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn text_goes_through_the_shifts() {
+        let text = Format::Text(CharacterSet::Uk);
+        let codes = text.read(b"Hello, world").unwrap();
+
+        assert_eq!(written(Format::Bletchley, &codes), b"HELLO5N98WORLD\n");
+        assert_eq!(written(text, &codes), b"HELLO, WORLD");
+
+        let pounds = text.read("£5".as_bytes()).unwrap();
+        assert_eq!(written(text.with_character_set(CharacterSet::UsTty), &pounds), b"#5");
+    }
+
+    #[test]
+    fn draws_the_holes_either_side_of_the_sprocket() {
+        assert_eq!(written(Format::Tape, &[0x03, 0x1C]), b"|oo .  |\n|  o.oo|\n");
+    }
+
+    #[test]
+    fn rejects_codes_out_of_range() {
+        assert_eq!(Format::Raw.read(&[1, 2, 32]), Err("byte 2 is not a 5-bit code".to_string()));
+        assert_eq!(Format::Hex.read(b"1f 20"), Err("\"20\" is not a 5-bit code in hex".to_string()));
+        assert!(Format::Bletchley.read(b"HT!").is_err());
+        assert_eq!(Format::Text(CharacterSet::Uk).read("a ~".as_bytes()), Err("'~' at 2 cannot be sent".to_string()));
+    }
+}
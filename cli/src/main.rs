@@ -0,0 +1,332 @@
+//! `lorenz`, the cipher core on the desktop.
+
+mod format;
+
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use lorenz::colossus::{Colossus, Program, Score};
+use lorenz::ita2::{Bletchley, CharacterSet};
+use lorenz::lorenz::Key;
+use lorenz::tutte::{Depth, Tutte};
+use lorenz::{LorenzMachine, MachineModel, MessageSettings, WheelPatterns};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::format::Format;
+
+const USAGE: &str = "\
+Usage: lorenz <command> [options] [file]
+
+Commands:
+  encrypt    Encipher a file, or standard input if none is given
+  decrypt    Decipher a file, or standard input if none is given
+  keygen     Write a random key which follows the cam rules
+  state      Print the machine state before a character of the message
+  colossus   Count a cipher against the key's chi patterns to set the chi wheels
+  tutte      Drag a crib through two cipher texts in depth, or write the key
+             Tutte's method recovers from the first one's plain text
+
+Options:
+  -k, --key <file>       The key file to set the machine from
+  -m, --model <model>    SZ40, SZ42A, SZ42B or Demo [default: SZ40]
+      --p5               Add the P5 limitation on the SZ42 models
+  -i, --input <format>   text, raw, bletchley, hex, tape or wav
+                         [default: text to encrypt, bletchley to decrypt]
+  -o, --output <format>  text, raw, bletchley, hex, tape or wav
+                         [default: bletchley from encrypt, text from decrypt]
+  -c, --charset <set>    The character set for text: ita2, uk, us-tty or mtk2
+                         [default: uk]
+      --shift <hz>       The shift for wav: 170 at 45.45 baud or 850 at 50 baud
+                         [default: 170]
+      --at <n>           The character to print the state at [default: 0]
+      --run <stages>     The colossus run, such as \"1+2=. total=3000; 3=.; 4=.; 5=.\"
+      --seed <n>         Seed keygen rather than using the system's randomness
+      --depth <file>     The cipher text sent in depth with the file's, for tutte
+      --crib <text>      The text tutte drags through the depth
+      --plain <file>     The plain text of the file read from the depth, for tutte
+  -h, --help             Print this message
+";
+
+/// How many of the best places for a crib `tutte` prints.
+const DRAG_POSITIONS: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Encrypt,
+    Decrypt,
+    Keygen,
+    State,
+    Colossus,
+    Tutte,
+}
+
+struct Options {
+    command: Command,
+    key: Option<PathBuf>,
+    model: MachineModel,
+    p5_limitation: bool,
+    input: Option<Format>,
+    output: Option<Format>,
+    character_set: CharacterSet,
+    shift: u16,
+    at: u64,
+    run: Option<String>,
+    seed: Option<u64>,
+    depth: Option<PathBuf>,
+    crib: Option<String>,
+    plain: Option<PathBuf>,
+    file: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let command = match args.next().as_deref() {
+        Some("encrypt") => Command::Encrypt,
+        Some("decrypt") => Command::Decrypt,
+        Some("keygen") => Command::Keygen,
+        Some("state") => Command::State,
+        Some("colossus") => Command::Colossus,
+        Some("tutte") => Command::Tutte,
+        Some(other) => return Err(format!("unknown command {other:?}")),
+        None => return Err("no command given".to_string()),
+    };
+
+    let mut options = Options {
+        command,
+        key: None,
+        model: MachineModel::SZ40,
+        p5_limitation: false,
+        input: None,
+        output: None,
+        character_set: CharacterSet::Uk,
+        shift: 170,
+        at: 0,
+        run: None,
+        seed: None,
+        depth: None,
+        crib: None,
+        plain: None,
+        file: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+
+        match arg.as_str() {
+            "-k" | "--key" => options.key = Some(value()?.into()),
+            "-m" | "--model" => options.model = parse_model(&value()?)?,
+            "--p5" => options.p5_limitation = true,
+            "-i" | "--input" => options.input = Some(parse_format(&value()?)?),
+            "-o" | "--output" => options.output = Some(parse_format(&value()?)?),
+            "-c" | "--charset" => options.character_set = parse_character_set(&value()?)?,
+            "--shift" => options.shift = parse_shift(&value()?)?,
+            "--at" => options.at = value()?.parse().map_err(|_| "--at needs a number".to_string())?,
+            "--run" => options.run = Some(value()?),
+            "--seed" => options.seed = Some(value()?.parse().map_err(|_| "--seed needs a number".to_string())?),
+            "--depth" => options.depth = Some(value()?.into()),
+            "--crib" => options.crib = Some(value()?),
+            "--plain" => options.plain = Some(value()?.into()),
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option {arg:?}")),
+            _ if options.file.is_none() => options.file = Some(arg.into()),
+            _ => return Err(format!("unexpected argument {arg:?}")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_model(name: &str) -> Result<MachineModel, String> {
+    [MachineModel::SZ40, MachineModel::SZ42A, MachineModel::SZ42B, MachineModel::Demo]
+        .into_iter()
+        .find(|model| model.to_string().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown model {name:?}"))
+}
+
+fn parse_format(name: &str) -> Result<Format, String> {
+    Format::from_name(name).ok_or_else(|| format!("unknown format {name:?}"))
+}
+
+fn parse_character_set(name: &str) -> Result<CharacterSet, String> {
+    match name {
+        "ita2" => Ok(CharacterSet::Ita2),
+        "uk" => Ok(CharacterSet::Uk),
+        "us-tty" => Ok(CharacterSet::UsTty),
+        "mtk2" => Ok(CharacterSet::Mtk2),
+        _ => Err(format!("unknown character set {name:?}")),
+    }
+}
+
+fn parse_shift(hz: &str) -> Result<u16, String> {
+    match hz {
+        "170" => Ok(170),
+        "850" => Ok(850),
+        _ => Err(format!("the shift must be 170 or 850, not {hz:?}")),
+    }
+}
+
+fn read_input(file: &Option<PathBuf>) -> Result<Vec<u8>, String> {
+    let mut input = Vec::new();
+
+    match file {
+        Some(path) if path.as_os_str() != "-" =>
+            input = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?,
+        _ => {
+            io::stdin().read_to_end(&mut input).map_err(|e| format!("standard input: {e}"))?;
+        }
+    }
+
+    Ok(input)
+}
+
+fn machine(options: &Options) -> Result<LorenzMachine, String> {
+    let path = options.key.as_ref().ok_or("a key file is needed, given with --key")?;
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let key: Key = text.parse().map_err(|e| format!("{}: {e}", path.display()))?;
+
+    // Key files only hold positions in range, so this cannot fail
+    let machine = LorenzMachine::from_key(&key).expect("key positions are checked when parsed");
+
+    Ok(machine.with_model(options.model).with_p5_limitation(options.p5_limitation))
+}
+
+/// Prints each stage's scores over its set total, going on from the best of
+/// each to the next, and then the best setting found.
+fn print_run(colossus: &Colossus, program: &Program, out: &mut impl Write) -> io::Result<()> {
+    let mut known = [None; 5];
+    let mut best: Option<Score> = None;
+
+    for (i, stage) in program.stages().iter().enumerate() {
+        writeln!(out, "stage {}", i + 1)?;
+
+        best = None;
+        for score in colossus.run(stage, known) {
+            if score.count > stage.total {
+                writeln!(out, "{score}")?;
+            }
+            if best.filter(|best| best.count >= score.count).is_none() {
+                best = Some(score);
+            }
+        }
+        known = best.map_or(known, |best| best.chi);
+    }
+
+    match best {
+        Some(best) => writeln!(out, "best {best}"),
+        None => Ok(()),
+    }
+}
+
+/// Prints the positions where the crib reads the most likely letters in the
+/// other message, with what it reads there.
+fn print_drag(depth: &Depth, crib: &[u8], out: &mut impl Write) -> io::Result<()> {
+    let likely = Format::Text(CharacterSet::default()).read(b"ETAOINSRH ").expect("letters are text");
+    let mut fits: Vec<(usize, usize)> = depth.drag(crib, |c| likely.contains(&c)).collect();
+    fits.sort_by_key(|&(at, count)| (std::cmp::Reverse(count), at));
+
+    for &(at, count) in fits.iter().take(DRAG_POSITIONS) {
+        let reading: Vec<u8> = depth.read(at, crib).collect();
+        writeln!(out, "at {at:<5} {count:<3} {}", Bletchley(&reading))?;
+    }
+    Ok(())
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let mut out = io::stdout().lock();
+
+    match options.command {
+        Command::Encrypt | Command::Decrypt => {
+            let mut machine = machine(&options)?;
+            let text = Format::Text(options.character_set);
+            let (input, output) = match options.command {
+                Command::Encrypt => (text, Format::Bletchley),
+                _ => (Format::Bletchley, text),
+            };
+            let input = options.input.unwrap_or(input).with_character_set(options.character_set).with_shift(options.shift);
+            let output = options.output.unwrap_or(output).with_character_set(options.character_set).with_shift(options.shift);
+            let codes = input.read(&read_input(&options.file)?)?;
+
+            let processed: Vec<u8> = if options.command == Command::Encrypt {
+                machine.process(codes).collect()
+            } else {
+                machine.decipher(codes).collect()
+            };
+
+            output.write(&processed, &mut out)
+        }
+        Command::Keygen => {
+            let mut rng = match options.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            };
+
+            let patterns = WheelPatterns::new_random(&mut rng);
+            let settings = MessageSettings::from_array(MessageSettings::WHEEL_LENGTHS.map(|n| rng.gen_range(0..n as u8)));
+
+            write!(out, "{}", Key { patterns, settings })
+        }
+        Command::State => {
+            let mut machine = machine(&options)?;
+            machine.seek(options.at).map_err(|_| "the P5 limitation needs the plaintext to find a character".to_string())?;
+
+            write!(out, "{}", machine.snapshot())
+        }
+        Command::Colossus => {
+            let patterns = machine(&options)?.patterns();
+            let run = options.run.as_deref().ok_or("a run is needed, given with --run")?;
+            let program = Program::parse(run).map_err(|e| format!("--run: {e}"))?;
+            let input = options.input.unwrap_or(Format::Bletchley).with_character_set(options.character_set).with_shift(options.shift);
+            let cipher = input.read(&read_input(&options.file)?)?;
+            let colossus = Colossus::new(&cipher, &patterns);
+
+            print_run(&colossus, &program, &mut out)
+        }
+        Command::Tutte => {
+            let input = options.input.unwrap_or(Format::Bletchley).with_character_set(options.character_set).with_shift(options.shift);
+            let text = Format::Text(options.character_set);
+            let first = input.read(&read_input(&options.file)?)?;
+
+            match (&options.crib, &options.plain) {
+                (Some(crib), _) => {
+                    let path = options.depth.as_ref().ok_or("the other message is needed, given with --depth")?;
+                    let second = input.read(&read_input(&Some(path.clone()))?)?;
+                    let crib = text.read(crib.as_bytes())?;
+
+                    print_drag(&Depth::new(&first, &second), &crib, &mut out)
+                }
+                (None, Some(path)) => {
+                    let plain = text.read(&read_input(&Some(path.clone()))?)?;
+                    let key: Vec<u8> = first.iter().zip(&plain).map(|(cipher, plain)| cipher ^ plain).collect();
+                    let key = Tutte::new(&key).recover().map_err(|e| e.to_string())?;
+
+                    write!(out, "{key}")
+                }
+                (None, None) => return Err("a crib or a plain text is needed, given with --crib or --plain".to_string()),
+            }
+        }
+    }
+    .and_then(|()| out.flush())
+    .map_err(|e| format!("standard output: {e}"))
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.is_empty() {
+        eprint!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    match parse_args(args.into_iter()).and_then(run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("lorenz: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,9 +1,6 @@
 #![no_std]
 #![no_main]
 
-mod ita2;
-mod lorenz;
-
 use arduino_hal::prelude::*;
 use panic_halt as _;
 use rand::SeedableRng;
@@ -11,8 +8,7 @@ use rand::SeedableRng;
 use smart_leds::{SmartLedsWrite, RGB8};
 
 use ws2812_spi::prerendered::Ws2812;
-use crate::ita2::EncoderOut;
-use crate::lorenz::LorenzMachine;
+use lorenz::{ita2, LorenzMachine};
 
 const ALL_TEST: [RGB8;LorenzMachine::OUTPUT_BUFFER_SIZE] = [RGB8::new(255, 0, 0); LorenzMachine::OUTPUT_BUFFER_SIZE];
 const ALL_OFF : [RGB8;LorenzMachine::OUTPUT_BUFFER_SIZE] = [RGB8::new(0,0,0); LorenzMachine::OUTPUT_BUFFER_SIZE];
@@ -44,8 +40,6 @@ fn main() -> ! {
     pins.d6.into_pull_up_input();
     pins.d7.into_pull_up_input();
 
-    arduino_hal::
-
     let (spi, _) = arduino_hal::spi::Spi::new(
         dp.SPI,
         pins.d52.into_output(),
@@ -68,52 +62,24 @@ fn main() -> ! {
     ws.write(ALL_OFF);
 
     let greeting_loop = GREETING.chars().into_iter().cycle();
-    let encoder = ita2::Encoder::new(greeting_loop);
+    let codes = ita2::Encoder::new(greeting_loop).codes();
     
     let mut rng = rand::rngs::StdRng::seed_from_u64(57);
     let mut machine = LorenzMachine::new_random(&mut rng);
     
     // let mut machine = LorenzMachine::new_zeroed();
 
-    for v in encoder {
-        match v {
-            EncoderOut::Single(c1) => {
-                // Write out first character
-                // let result = machine.encode_at_step(c1);
-                
-                let out = machine.draw().map(
-                    |x| if x { COLOR_ON } else { COLOR_OFF });
-                ws.write(out).unwrap();
-            },
-            EncoderOut::ShiftAndChar(c1,c2) => {
-                // Write out first character
-                let result = machine.encode_at_step(c1);
-                
-                let out = machine.draw().map(
-                    |x| if x { COLOR_ON } else { COLOR_OFF });
-                ws.write(out).unwrap();
-
-                // Wait for effect!
-                arduino_hal::delay_ms(WAIT_DELAY);
-
-                // Step the encoder
-                machine.step_machine();
-
-                // Write out second character
-                // let result = machine.encode_at_step(c2);
-                
-                let out = machine.draw().map(
-                    |x| if x { COLOR_ON } else { COLOR_OFF });
-                ws.write(out).unwrap();
-            }
-        }
+    let mut cipher = machine.process(codes);
+
+    loop {
+        let out = cipher.machine().draw().map(
+            |x| if x { COLOR_ON } else { COLOR_OFF });
+        ws.write(out).unwrap();
 
         // Wait for effect!
         arduino_hal::delay_ms(WAIT_DELAY);
 
-        // Step the encoder
-        machine.step_machine();
+        // Encipher the next character and step the machine
+        cipher.next();
     }
-    
-    unreachable!()
 }
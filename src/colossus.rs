@@ -0,0 +1,327 @@
+//! A software Colossus, which finds the chi wheel settings of a message by
+//! counting, for every setting of the wheels stepped, how often a condition
+//! on the cipher added to the chi wheels holds.
+//!
+//! The cipher Z is the plain text added to χ and ψ'. Taking off the right χ
+//! leaves Q = P + ψ', and since ψ' stands still for about half the characters
+//! and plain text repeats itself, ΔQ is a dot more often than chance. The
+//! standard first run is the 1+2 break-in, counting ΔQ1 + ΔQ2 = . over all
+//! 41 × 31 settings of χ1 and χ2; the right setting counts highest.
+
+mod plugboard;
+
+use core::fmt;
+
+use crate::lorenz::{MessageSettings, WheelPatterns};
+
+pub use plugboard::{PlugboardError, Program, Stage, MAX_CONDITIONS, MAX_STAGES};
+
+/// Counts where the impulses in `mask` add to a cross, or to a dot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Condition {
+    /// The impulses added, impulse 1 in the least significant bit
+    pub mask: u8,
+    pub cross: bool,
+}
+
+impl Condition {
+    pub fn holds(&self, q: u8) -> bool {
+        ((q & self.mask).count_ones() & 1 == 1) == self.cross
+    }
+}
+
+/// What is counted at each character: all of the conditions on ΔQ, or on Q.
+/// This is the counting engine both `Colossus` and the Heath Robinson run on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Test {
+    conditions: [Condition; MAX_CONDITIONS],
+    len: usize,
+    pub delta: bool,
+}
+
+impl Test {
+    /// Counts where all of `conditions` hold on ΔQ. Panics if there are more
+    /// than `MAX_CONDITIONS`, as the plugboard has no room for them.
+    pub fn new(conditions: &[Condition]) -> Self {
+        assert!(conditions.len() <= MAX_CONDITIONS, "a test can only count {MAX_CONDITIONS} conditions");
+
+        let len = conditions.len();
+        let mut test = Test {
+            conditions: [Condition { mask: 0, cross: false }; MAX_CONDITIONS],
+            len,
+            delta: true,
+        };
+        test.conditions[..len].copy_from_slice(&conditions[..len]);
+        test
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions[..self.len]
+    }
+
+    /// The chi wheels the conditions read, impulse 1 in the least significant bit.
+    pub fn impulses(&self) -> u8 {
+        self.conditions().iter().fold(0, |mask, condition| mask | condition.mask)
+    }
+
+    pub fn holds(&self, q: u8) -> bool {
+        self.conditions().iter().all(|condition| condition.holds(q))
+    }
+
+    /// The number of characters the test holds for, with the cipher in
+    /// `cipher` and the chi wheels in `chi`, read side by side.
+    pub fn count(&self, cipher: impl Iterator<Item = u8>, chi: impl Iterator<Item = u8>) -> usize {
+        let mut q = cipher.zip(chi).map(|(z, chi)| z ^ chi);
+        let mut previous = if self.delta { q.next() } else { None };
+
+        q.filter(|&q| {
+            let counted = match previous {
+                Some(previous) => q ^ previous,
+                None => q,
+            };
+            if self.delta {
+                previous = Some(q);
+            }
+            self.holds(counted)
+        })
+        .count()
+    }
+}
+
+/// The chi wheels read from given settings, leaving out the wheels with none.
+#[derive(Clone, Debug)]
+pub struct ChiTape<'a> {
+    wheels: [&'a [bool]; 5],
+    positions: [Option<usize>; 5],
+}
+
+impl<'a> ChiTape<'a> {
+    pub fn new(patterns: &'a WheelPatterns, settings: [Option<u8>; 5]) -> Self {
+        ChiTape {
+            wheels: [&patterns.chi_1, &patterns.chi_2, &patterns.chi_3, &patterns.chi_4, &patterns.chi_5],
+            positions: settings.map(|setting| setting.map(usize::from)),
+        }
+    }
+}
+
+impl Iterator for ChiTape<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chi = 0;
+        for (i, (wheel, position)) in self.wheels.iter().zip(self.positions.iter_mut()).enumerate() {
+            if let Some(position) = position {
+                chi |= (wheel[*position] as u8) << i;
+                *position = if *position + 1 == wheel.len() { 0 } else { *position + 1 };
+            }
+        }
+
+        Some(chi)
+    }
+}
+
+/// The count for one setting of the chi wheels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Score {
+    /// The settings of the chi wheels counted with, `None` where a wheel was left out
+    pub chi: [Option<u8>; 5],
+    pub count: usize,
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, setting) in MessageSettings::WHEEL_NAMES[7..].iter().zip(self.chi) {
+            if let Some(setting) = setting {
+                write!(f, "{name}={setting:<2} ")?;
+            }
+        }
+        write!(f, "{}", self.count)
+    }
+}
+
+/// A cipher tape and the chi patterns to count it against.
+#[derive(Clone, Copy, Debug)]
+pub struct Colossus<'a> {
+    cipher: &'a [u8],
+    patterns: &'a WheelPatterns,
+}
+
+impl<'a> Colossus<'a> {
+    pub fn new(cipher: &'a [u8], patterns: &'a WheelPatterns) -> Self {
+        Colossus { cipher, patterns }
+    }
+
+    /// The count for `stage` at every setting of the wheels it steps, with the
+    /// wheels in `known` set where the stage does not set them itself.
+    pub fn run(&self, stage: &'a Stage, known: [Option<u8>; 5]) -> Scores<'a> {
+        let mut base = known;
+        for (setting, set) in base.iter_mut().zip(stage.set) {
+            *setting = set.or(*setting);
+        }
+
+        let stepped = stage.step.unwrap_or_else(|| {
+            let read = stage.test.impulses();
+            core::array::from_fn(|i| read >> i & 1 == 1 && base[i].is_none())
+        });
+
+        let mut first = base;
+        for (setting, stepped) in first.iter_mut().zip(stepped) {
+            if stepped {
+                *setting = Some(0);
+            }
+        }
+
+        Scores { colossus: *self, stage, stepped, next: Some(first) }
+    }
+
+    /// The best-counting setting of `stage`, the first if several tie.
+    pub fn best(&self, stage: &'a Stage, known: [Option<u8>; 5]) -> Score {
+        self.run(stage, known)
+            .reduce(|best, score| if score.count > best.count { score } else { best })
+            .expect("every run counts at least one setting")
+    }
+
+    /// Runs every stage of `program` in turn, each from the best setting of
+    /// the ones before it, giving the chi settings found.
+    pub fn settle(&self, program: &'a Program) -> [Option<u8>; 5] {
+        program.stages().iter().fold([None; 5], |known, stage| self.best(stage, known).chi)
+    }
+
+    fn count(&self, test: &Test, span: Option<(usize, usize)>, settings: [Option<u8>; 5]) -> usize {
+        let (start, end) = span.unwrap_or((0, self.cipher.len()));
+        let end = end.min(self.cipher.len());
+        let start = start.min(end);
+
+        // The chi wheels have moved on by the first character counted
+        let settings: [Option<u8>; 5] = core::array::from_fn(|i| {
+            let length = MessageSettings::WHEEL_LENGTHS[7 + i];
+            settings[i].map(|setting| ((setting as usize + start) % length) as u8)
+        });
+
+        test.count(self.cipher[start..end].iter().copied(), ChiTape::new(self.patterns, settings))
+    }
+}
+
+/// Every setting of a stage's stepped wheels with its count, the fastest
+/// stepping wheel the highest numbered.
+pub struct Scores<'a> {
+    colossus: Colossus<'a>,
+    stage: &'a Stage,
+    stepped: [bool; 5],
+    next: Option<[Option<u8>; 5]>,
+}
+
+impl Iterator for Scores<'_> {
+    type Item = Score;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chi = self.next?;
+        let count = self.colossus.count(&self.stage.test, self.stage.span, chi);
+
+        // Step the wheels on like an odometer, finishing once all have gone round
+        let mut next = chi;
+        self.next = None;
+        for i in (0..5).rev().filter(|&i| self.stepped[i]) {
+            let setting = next[i].unwrap_or(0) + 1;
+            if (setting as usize) < MessageSettings::WHEEL_LENGTHS[7 + i] {
+                next[i] = Some(setting);
+                self.next = Some(next);
+                break;
+            }
+            next[i] = Some(0);
+        }
+
+        Some(Score { chi, count })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Chi patterns for counting tests, shared with the Heath Robinson's.
+    pub(crate) fn patterns() -> WheelPatterns {
+        let mut patterns = WheelPatterns::new_zeroed();
+        for wheel in 7..12 {
+            for (i, cam) in patterns.wheel_mut(wheel).iter_mut().enumerate() {
+                *cam = (i * 7 + wheel) % 5 < 2;
+            }
+        }
+        patterns
+    }
+
+    #[test]
+    fn conditions_add_impulses() {
+        let one_plus_two = Condition { mask: 0b00011, cross: false };
+
+        assert!(one_plus_two.holds(0b00000));
+        assert!(one_plus_two.holds(0b11111));
+        assert!(!one_plus_two.holds(0b00010));
+        assert!(Condition { mask: 0b00100, cross: true }.holds(0b00100));
+    }
+
+    #[test]
+    #[should_panic(expected = "a test can only count 8 conditions")]
+    fn refuses_more_conditions_than_the_plugboard_holds() {
+        Test::new(&[Condition { mask: 0b00001, cross: true }; MAX_CONDITIONS + 1]);
+    }
+
+    #[test]
+    fn counts_on_the_delta_or_the_plain_stream() {
+        let mut test = Test::new(&[Condition { mask: 0b00001, cross: true }]);
+        let cipher = [0, 1, 1, 0, 0, 1];
+
+        assert_eq!(test.count(cipher.into_iter(), core::iter::repeat(0)), 3);
+        test.delta = false;
+        assert_eq!(test.count(cipher.into_iter(), core::iter::repeat(0)), 3);
+        assert_eq!(test.count(cipher.into_iter(), core::iter::repeat(1)), 3);
+        assert_eq!(test.count(cipher.into_iter().take(4), core::iter::repeat(1)), 2);
+    }
+
+    #[test]
+    fn reads_the_chi_wheels_from_their_settings() {
+        let patterns = patterns();
+        let chi: [u8; 42] = {
+            let mut tape = ChiTape::new(&patterns, [Some(40), None, Some(0), None, None]);
+            core::array::from_fn(|_| tape.next().unwrap())
+        };
+
+        assert_eq!(chi[0], patterns.chi_1[40] as u8 | (patterns.chi_3[0] as u8) << 2);
+        assert_eq!(chi[1] & 1, patterns.chi_1[0] as u8);
+        assert_eq!(chi[30] >> 2 & 1, patterns.chi_3[1] as u8);
+        assert!(chi.iter().all(|&c| c & 0b11010 == 0));
+    }
+
+    #[test]
+    fn steps_every_setting_of_the_wheels_read() {
+        let patterns = patterns();
+        let cipher = [0u8; 100];
+        let colossus = Colossus::new(&cipher, &patterns);
+        let stage = Stage::parse("1+2=.").unwrap();
+
+        let mut scores = colossus.run(&stage, [None; 5]);
+        assert_eq!(scores.next().map(|s| s.chi), Some([Some(0), Some(0), None, None, None]));
+        assert_eq!(scores.next().map(|s| s.chi), Some([Some(0), Some(1), None, None, None]));
+        assert_eq!(scores.count(), 41 * 31 - 2);
+
+        let fixed = Stage::parse("1+2=. chi2=3").unwrap();
+        assert_eq!(colossus.run(&fixed, [None, None, Some(5), None, None]).last().map(|s| s.chi),
+            Some([Some(40), Some(3), Some(5), None, None]));
+    }
+
+    #[test]
+    fn finds_a_setting_the_cipher_was_made_with() {
+        let patterns = patterns();
+        let settings = [Some(17), Some(9), Some(4), Some(20), Some(11)];
+        // A cipher of chi alone counts every character where ΔQ is all dots
+        let cipher: [u8; 600] = {
+            let mut chi = ChiTape::new(&patterns, settings);
+            core::array::from_fn(|_| chi.next().unwrap())
+        };
+        let colossus = Colossus::new(&cipher, &patterns);
+        let program = Program::parse("1=. 2=.; 3=. span=100-400; 4=. 5=.").unwrap();
+
+        assert_eq!(colossus.best(&program.stages()[0], [None; 5]), Score { chi: [Some(17), Some(9), None, None, None], count: 599 });
+        assert_eq!(colossus.settle(&program), settings);
+    }
+}
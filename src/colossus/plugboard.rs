@@ -0,0 +1,248 @@
+//! The plugboard language a run is set up in, one stage to a line or
+//! separated by `;`. Each stage is a list of words:
+//!
+//! - `1+2=.` counts where ΔQ1 + ΔQ2 is a dot, and `3=x` where ΔQ3 is a cross.
+//!   Q is the cipher added to the chi wheels. Every condition in a stage must
+//!   hold for a character to count.
+//! - `step=12` steps χ1 and χ2 through every setting. Without it the stage
+//!   steps every chi wheel its conditions read which has no setting yet.
+//! - `chi3=12` sets χ3 at 12 rather than stepping it.
+//! - `span=0-500` counts only those characters, for a short run.
+//! - `total=3000` prints only the settings counting more than 3000.
+//! - `plain` counts on Q rather than ΔQ.
+//!
+//! A later stage starts from the best setting of the stages before it, so
+//! `1+2=. total=3000; 4=.; 5=.` sets χ1 and χ2, then χ4, then χ5.
+
+use core::fmt;
+
+use super::{Condition, Test};
+use crate::lorenz::MessageSettings;
+
+/// The most conditions a stage can count.
+pub const MAX_CONDITIONS: usize = 8;
+
+/// The most stages a run can have.
+pub const MAX_STAGES: usize = 8;
+
+/// Why a run could not be read. `at` is the byte offset of the word at fault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlugboardError {
+    /// The word is neither a condition nor an option.
+    UnknownWord { at: usize },
+    /// A condition or wheel list names something other than impulses 1 to 5.
+    NotAnImpulse { at: usize, found: char },
+    /// A condition does not end in `=.` or `=x`.
+    MissingResult { at: usize },
+    /// A condition's impulses are missing or cancel out, so it would hold
+    /// on every character.
+    EmptyCondition { at: usize },
+    /// An option's value is not a number, or not a range of numbers.
+    NotANumber { at: usize },
+    /// A chi wheel is set past its length.
+    PositionOutOfRange { at: usize, wheel: u8, position: usize },
+    /// A stage has no conditions to count.
+    NoConditions { at: usize },
+    TooManyConditions { at: usize },
+    TooManyStages { at: usize },
+    /// The run has no stages at all.
+    NoStages,
+}
+
+impl fmt::Display for PlugboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PlugboardError::UnknownWord { at } => write!(f, "at {at}: not a condition or an option"),
+            PlugboardError::NotAnImpulse { at, found } => write!(f, "at {at}: {found:?} is not an impulse from 1 to 5"),
+            PlugboardError::MissingResult { at } => write!(f, "at {at}: a condition must end in =. or =x"),
+            PlugboardError::EmptyCondition { at } => write!(f, "at {at}: the condition adds no impulses"),
+            PlugboardError::NotANumber { at } => write!(f, "at {at}: expected a number"),
+            PlugboardError::PositionOutOfRange { at, wheel, position } =>
+                write!(f, "at {at}: chi{wheel} has no position {position}"),
+            PlugboardError::NoConditions { at } => write!(f, "at {at}: the stage counts nothing"),
+            PlugboardError::TooManyConditions { at } =>
+                write!(f, "at {at}: a stage can only count {MAX_CONDITIONS} conditions"),
+            PlugboardError::TooManyStages { at } => write!(f, "at {at}: a run can only have {MAX_STAGES} stages"),
+            PlugboardError::NoStages => write!(f, "the run has no stages"),
+        }
+    }
+}
+
+/// One counting pass over the cipher.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stage {
+    pub test: Test,
+    /// The chi wheels stepped, or `None` for those the conditions read
+    pub step: Option<[bool; 5]>,
+    /// Chi wheels set for this stage
+    pub set: [Option<u8>; 5],
+    /// The characters counted, or `None` for the whole cipher
+    pub span: Option<(usize, usize)>,
+    /// Settings counting more than this are printed
+    pub total: usize,
+}
+
+/// Stages to be run one after the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Program {
+    stages: [Stage; MAX_STAGES],
+    len: usize,
+}
+
+/// The byte offset of `part` within `whole`.
+fn offset(whole: &str, part: &str) -> usize {
+    part.as_ptr() as usize - whole.as_ptr() as usize
+}
+
+fn impulse(c: char, at: usize) -> Result<u8, PlugboardError> {
+    match c {
+        '1'..='5' => Ok(c as u8 - b'0'),
+        found => Err(PlugboardError::NotAnImpulse { at, found }),
+    }
+}
+
+fn number(text: &str, at: usize) -> Result<usize, PlugboardError> {
+    text.parse().map_err(|_| PlugboardError::NotANumber { at })
+}
+
+impl Stage {
+    pub fn parse(text: &str) -> Result<Stage, PlugboardError> {
+        Self::parse_at(text, text)
+    }
+
+    /// Parses a stage, reporting offsets within `whole`.
+    fn parse_at(whole: &str, text: &str) -> Result<Stage, PlugboardError> {
+        let mut conditions = [Condition { mask: 0, cross: false }; MAX_CONDITIONS];
+        let mut len = 0;
+        let mut stage = Stage {
+            test: Test::new(&[]),
+            step: None,
+            set: [None; 5],
+            span: None,
+            total: 0,
+        };
+
+        for word in text.split_whitespace() {
+            let at = offset(whole, word);
+
+            match word.split_once('=') {
+                Some(("step", wheels)) => {
+                    let mut step = [false; 5];
+                    for c in wheels.chars() {
+                        step[impulse(c, at)? as usize - 1] = true;
+                    }
+                    stage.step = Some(step);
+                }
+                Some(("span", range)) => {
+                    let (start, end) = range.split_once('-').ok_or(PlugboardError::NotANumber { at })?;
+                    stage.span = Some((number(start, at)?, number(end, at)?));
+                }
+                Some(("total", total)) => stage.total = number(total, at)?,
+                Some((name, position)) if name.starts_with("chi") => {
+                    let mut digits = name[3..].chars();
+                    let wheel = match (digits.next(), digits.next()) {
+                        (Some(c), None) => impulse(c, at)?,
+                        _ => return Err(PlugboardError::UnknownWord { at }),
+                    };
+                    let position = number(position, at)?;
+                    if position >= MessageSettings::WHEEL_LENGTHS[6 + wheel as usize] {
+                        return Err(PlugboardError::PositionOutOfRange { at, wheel, position });
+                    }
+                    stage.set[wheel as usize - 1] = Some(position as u8);
+                }
+                Some((impulses, result)) => {
+                    let mut mask = 0;
+                    for c in impulses.chars().filter(|&c| c != '+') {
+                        mask ^= 1 << (impulse(c, at)? - 1);
+                    }
+                    if mask == 0 {
+                        return Err(PlugboardError::EmptyCondition { at });
+                    }
+                    let cross = match result {
+                        "." => false,
+                        "x" | "X" => true,
+                        _ => return Err(PlugboardError::MissingResult { at }),
+                    };
+
+                    *conditions.get_mut(len).ok_or(PlugboardError::TooManyConditions { at })? = Condition { mask, cross };
+                    len += 1;
+                }
+                None if word == "plain" => stage.test.delta = false,
+                None => return Err(PlugboardError::UnknownWord { at }),
+            }
+        }
+
+        if len == 0 {
+            return Err(PlugboardError::NoConditions { at: offset(whole, text) });
+        }
+
+        stage.test = Test { delta: stage.test.delta, ..Test::new(&conditions[..len]) };
+        Ok(stage)
+    }
+}
+
+impl Program {
+    pub fn parse(text: &str) -> Result<Program, PlugboardError> {
+        let mut program = Program {
+            stages: [Stage::parse("1=.").expect("a single condition parses"); MAX_STAGES],
+            len: 0,
+        };
+
+        for part in text.split([';', '\n']).filter(|part| !part.trim().is_empty()) {
+            let at = offset(text, part);
+            let stage = Stage::parse_at(text, part)?;
+
+            *program.stages.get_mut(program.len).ok_or(PlugboardError::TooManyStages { at })? = stage;
+            program.len += 1;
+        }
+
+        if program.len == 0 {
+            return Err(PlugboardError::NoStages);
+        }
+        Ok(program)
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_conditions_and_options() {
+        let stage = Stage::parse("1+2=. 3=x step=12 chi3=28 span=10-500 total=3000 plain").unwrap();
+
+        assert_eq!(stage.test.conditions(), [Condition { mask: 0b00011, cross: false }, Condition { mask: 0b00100, cross: true }]);
+        assert!(!stage.test.delta);
+        assert_eq!(stage.step, Some([true, true, false, false, false]));
+        assert_eq!(stage.set, [None, None, Some(28), None, None]);
+        assert_eq!(stage.span, Some((10, 500)));
+        assert_eq!(stage.total, 3000);
+    }
+
+    #[test]
+    fn splits_stages() {
+        let program = Program::parse("1+2=. total=3000; 4=.\n\n5=. ").unwrap();
+
+        assert_eq!(program.stages().len(), 3);
+        assert!(program.stages()[1].test.delta);
+        assert_eq!(program.stages()[2].test.conditions(), [Condition { mask: 0b10000, cross: false }]);
+    }
+
+    #[test]
+    fn reports_where_a_run_is_wrong() {
+        assert_eq!(Program::parse("1+2=.; 1+6=."), Err(PlugboardError::NotAnImpulse { at: 7, found: '6' }));
+        assert_eq!(Stage::parse("1+2=o"), Err(PlugboardError::MissingResult { at: 0 }));
+        assert_eq!(Stage::parse("3=x 1+1=."), Err(PlugboardError::EmptyCondition { at: 4 }));
+        assert_eq!(Stage::parse("=."), Err(PlugboardError::EmptyCondition { at: 0 }));
+        assert_eq!(Stage::parse("1=. chi2=31"), Err(PlugboardError::PositionOutOfRange { at: 4, wheel: 2, position: 31 }));
+        assert_eq!(Stage::parse("1=. total=lots"), Err(PlugboardError::NotANumber { at: 4 }));
+        assert_eq!(Stage::parse("1=. fast"), Err(PlugboardError::UnknownWord { at: 4 }));
+        assert_eq!(Program::parse("1=.; step=1"), Err(PlugboardError::NoConditions { at: 4 }));
+        assert_eq!(Program::parse(""), Err(PlugboardError::NoStages));
+        assert_eq!(Program::parse(" ;\n; "), Err(PlugboardError::NoStages));
+    }
+}
@@ -0,0 +1,124 @@
+//! Differenced streams, the ground every Tunny attack was worked on.
+//!
+//! The delta of a stream adds each character to the one after it, so ΔZ has
+//! one character fewer than Z. Addition is bitwise exclusive or, impulse by
+//! impulse, which Bletchley Park wrote as `+` between letters: `H + T = 9`.
+//! Because the chi wheels step every character and the psi wheels often stand
+//! still, ΔZ = ΔP + Δχ + Δψ' leans towards Δχ, which is what Colossus counted.
+
+use core::ops::BitXor;
+
+use crate::ita2::{from_bletchley, to_bletchley};
+
+/// The sum of two characters in Bletchley notation, such as `H + T = 9`.
+pub fn add(a: char, b: char) -> Option<char> {
+    Some(to_bletchley(from_bletchley(a)? ^ from_bletchley(b)?))
+}
+
+/// The delta of a stream of codes or of bits.
+pub struct Delta<T>
+where
+    T: Iterator,
+{
+    source: T,
+    previous: Option<T::Item>,
+}
+
+impl<T: Iterator> Delta<T>
+where
+    T::Item: BitXor<Output = T::Item> + Copy,
+{
+    pub fn new(source: T) -> Self {
+        Delta { source, previous: None }
+    }
+}
+
+impl<T: Iterator> Iterator for Delta<T>
+where
+    T::Item: BitXor<Output = T::Item> + Copy,
+{
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => self.source.next()?,
+        };
+        let next = self.source.next()?;
+        self.previous = Some(next);
+
+        Some(previous ^ next)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.source.size_hint();
+        let pending = self.previous.is_some() as usize;
+        let less_one = |n: usize| (n + pending).saturating_sub(1);
+
+        (less_one(lower), upper.map(less_one))
+    }
+}
+
+/// One impulse of a stream of codes, `true` for a cross.
+pub struct Impulse<T>
+where
+    T: Iterator<Item = u8>,
+{
+    source: T,
+    shift: u8,
+}
+
+impl<T: Iterator<Item = u8>> Impulse<T> {
+    /// Impulse `impulse`, 1 to 5, of each code.
+    pub fn new(source: T, impulse: u8) -> Self {
+        debug_assert!((1..=5).contains(&impulse));
+        Impulse { source, shift: impulse - 1 }
+    }
+}
+
+impl<T: Iterator<Item = u8>> Iterator for Impulse<T> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.source.next()? >> self.shift & 1 == 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ita2::FromBletchley;
+
+    fn codes(text: &str) -> impl Iterator<Item = u8> + '_ {
+        FromBletchley::new(text.chars()).map(Result::unwrap)
+    }
+
+    #[test]
+    fn adds_letters() {
+        assert_eq!(add('H', 'T'), Some('9'));
+        assert_eq!(add('E', 'E'), Some('/'));
+        assert_eq!(add('m', '/'), Some('M'));
+        assert_eq!(add('H', '!'), None);
+    }
+
+    #[test]
+    fn adds_each_character_to_the_next() {
+        assert_eq!(Delta::new([1u8, 2, 3, 4].into_iter()).size_hint(), (3, Some(3)));
+        assert!(Delta::new(codes("HT9/")).eq(codes("9H9")));
+        assert_eq!(Delta::new(codes("H")).next(), None);
+        assert!(Delta::new([true, true, false, true].into_iter()).eq([false, true, true]));
+    }
+
+    #[test]
+    fn splits_out_impulses() {
+        // M is 00111 in impulses 1 to 5
+        let impulses: [bool; 5] = core::array::from_fn(|i| Impulse::new(codes("M"), i as u8 + 1).next().unwrap());
+
+        assert_eq!(impulses, [false, false, true, true, true]);
+        assert!(Impulse::new(Delta::new(codes("HT9/")), 3).eq(Delta::new(Impulse::new(codes("HT9/"), 3))));
+    }
+}
@@ -0,0 +1,369 @@
+//! Start/stop framing as a teleprinter sends it on the line: a start bit of
+//! space, the five impulses with impulse 1 first, then mark for the stop bits.
+//! The line rests at mark between characters.
+//!
+//! The line is a stream of samples taken at a fixed rate, `true` for a high
+//! level, so the same code drives a pin, a modem or a test.
+
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StopBits {
+    One,
+    /// The usual length for 45.45 baud US teletypes
+    OneAndAHalf,
+    /// The usual length for 50 baud European teleprinters
+    Two,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Polarity {
+    /// Mark is the high level, as on a current loop
+    #[default]
+    MarkHigh,
+    MarkLow,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Framing {
+    baud: f32,
+    stop_bits: StopBits,
+    polarity: Polarity,
+}
+
+/// Why a line could not be timed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateError {
+    /// The baud is not a number of bits a second above 0.
+    InvalidBaud { baud: f32 },
+    /// There are no samples to send or read the bits in.
+    ZeroSampleRate,
+}
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RateError::InvalidBaud { baud } => write!(f, "{baud} is not a baud above 0"),
+            RateError::ZeroSampleRate => write!(f, "the sample rate is 0"),
+        }
+    }
+}
+
+impl Framing {
+    /// 50 baud with two stop bits, the European standard and the Tunny link.
+    pub const BAUD_50: Framing = Framing { baud: 50.0, stop_bits: StopBits::Two, polarity: Polarity::MarkHigh };
+
+    /// 45.45 baud with one and a half stop bits, the US amateur standard.
+    pub const BAUD_45: Framing = Framing { baud: 45.45, stop_bits: StopBits::OneAndAHalf, polarity: Polarity::MarkHigh };
+
+    /// `baud` bits a second with two stop bits and mark high.
+    pub fn new(baud: f32) -> Result<Framing, RateError> {
+        if !(baud.is_finite() && baud > 0.0) {
+            return Err(RateError::InvalidBaud { baud });
+        }
+        Ok(Framing { baud, ..Framing::BAUD_50 })
+    }
+
+    pub fn baud(&self) -> f32 {
+        self.baud
+    }
+
+    pub fn stop_bits(&self) -> StopBits {
+        self.stop_bits
+    }
+
+    pub fn polarity(&self) -> Polarity {
+        self.polarity
+    }
+
+    pub fn with_stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// The length of a character in bits, start and stop bits included.
+    pub fn character_bits(&self) -> f32 {
+        6.0 + match self.stop_bits {
+            StopBits::One => 1.0,
+            StopBits::OneAndAHalf => 1.5,
+            StopBits::Two => 2.0,
+        }
+    }
+
+    /// The line level for mark (`true`) or space (`false`), which is also
+    /// whether a line level is mark.
+    pub(crate) fn level(&self, mark: bool) -> bool {
+        mark == (self.polarity == Polarity::MarkHigh)
+    }
+
+    /// Mark or space for bit `bit` of a character, counting the start bit as 0.
+    fn bit(code: u8, bit: usize) -> bool {
+        match bit {
+            0 => false,
+            1..=5 => code >> (bit - 1) & 1 == 1,
+            _ => true,
+        }
+    }
+
+    /// The samples for a stream of codes, sent back to back.
+    pub fn transmit<T: Iterator<Item = u8>>(&self, source: T, sample_rate: u32) -> Result<Transmitter<T>, RateError> {
+        if sample_rate == 0 {
+            return Err(RateError::ZeroSampleRate);
+        }
+
+        Ok(Transmitter {
+            framing: *self,
+            samples_per_bit: sample_rate as f32 / self.baud,
+            source,
+            code: 0,
+            sample: 0,
+            length: 0,
+            carry: 0.0,
+        })
+    }
+
+    /// Reads codes back out of a stream of samples.
+    pub fn receive<T: Iterator<Item = bool>>(&self, source: T, sample_rate: u32) -> Result<Receiver<T>, RateError> {
+        if sample_rate == 0 {
+            return Err(RateError::ZeroSampleRate);
+        }
+
+        Ok(Receiver {
+            framing: *self,
+            samples_per_bit: sample_rate as f32 / self.baud,
+            source,
+            sample: 0,
+            state: State::Idle,
+        })
+    }
+}
+
+/// The line samples for a stream of codes. Only the low five bits of each
+/// code are sent.
+pub struct Transmitter<T>
+where
+    T: Iterator<Item = u8>,
+{
+    framing: Framing,
+    samples_per_bit: f32,
+    source: T,
+    code: u8,
+    /// The next sample within the current character
+    sample: u32,
+    /// The current character's length in samples
+    length: u32,
+    /// The part of a sample left over from the characters so far
+    carry: f32,
+}
+
+impl<T: Iterator<Item = u8>> Iterator for Transmitter<T> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // At a very low sample rate a character can round to no samples at all
+        while self.sample == self.length {
+            self.code = self.source.next()?;
+
+            // Keep characters in step with the baud over a long message,
+            // even when a character is not a whole number of samples
+            let exact = self.framing.character_bits() * self.samples_per_bit + self.carry;
+            self.length = (exact + 0.5) as u32;
+            self.carry = exact - self.length as f32;
+            self.sample = 0;
+        }
+
+        let bit = ((self.sample as f32 + 0.5) / self.samples_per_bit) as usize;
+        self.sample += 1;
+
+        Some(self.framing.level(Framing::bit(self.code, bit)))
+    }
+}
+
+/// A character whose stop bit was space, as happens when the receiver has
+/// lost step with the sender or the line was broken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramingError {
+    /// The sample the character's start bit began at, counting from 0
+    pub start: usize,
+    /// The impulses as they were read
+    pub code: u8,
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no stop bit after the character starting at sample {}", self.start)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Waiting for the line to fall from mark to space
+    Idle,
+    /// Waiting for the line to return to mark after a framing error
+    Break,
+    /// Within a character which started at `start`, next reading bit `bit`
+    Character { start: usize, bit: usize, code: u8 },
+}
+
+/// Samples a line back into codes, as a teleprinter's receiving magnet and
+/// distributor would.
+///
+/// A fall from mark to space starts a character, and each bit is read in its
+/// middle. A start bit which is no longer space there is taken for noise. A
+/// stop bit which is not mark gives a `FramingError`, after which the line
+/// must return to mark before another character can start.
+pub struct Receiver<T>
+where
+    T: Iterator<Item = bool>,
+{
+    framing: Framing,
+    samples_per_bit: f32,
+    source: T,
+    /// The index of the next sample
+    sample: usize,
+    state: State,
+}
+
+impl<T: Iterator<Item = bool>> Receiver<T> {
+    /// The sample the middle of `bit` falls on, for a character from `start`.
+    fn middle(&self, start: usize, bit: usize) -> usize {
+        start + ((bit as f32 + 0.5) * self.samples_per_bit) as usize
+    }
+}
+
+impl<T: Iterator<Item = bool>> Iterator for Receiver<T> {
+    type Item = Result<u8, FramingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let index = self.sample;
+            let mark = self.framing.level(self.source.next()?);
+            self.sample += 1;
+
+            match self.state {
+                State::Idle if !mark => self.state = State::Character { start: index, bit: 0, code: 0 },
+                State::Break if mark => self.state = State::Idle,
+                State::Idle | State::Break => {}
+                State::Character { start, bit, code } => {
+                    if index < self.middle(start, bit) {
+                        continue;
+                    }
+
+                    self.state = match bit {
+                        0 if mark => State::Idle,
+                        0 => State::Character { start, bit: 1, code },
+                        1..=5 => State::Character { start, bit: bit + 1, code: code | (mark as u8) << (bit - 1) },
+                        _ if mark => {
+                            self.state = State::Idle;
+                            return Some(Ok(code));
+                        }
+                        _ => {
+                            self.state = State::Break;
+                            return Some(Err(FramingError { start, code }));
+                        }
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 8000;
+
+    fn codes() -> impl Iterator<Item = u8> + Clone {
+        (0..200u16).map(|i| (i * 11 % 32) as u8)
+    }
+
+    #[test]
+    fn round_trips_at_the_standard_speeds() {
+        for framing in [Framing::BAUD_50, Framing::BAUD_45, Framing::BAUD_50.with_stop_bits(StopBits::One)] {
+            let line = framing.transmit(codes(), RATE).unwrap();
+
+            assert!(framing.receive(line, RATE).unwrap().map(Result::unwrap).eq(codes()), "{framing:?}");
+        }
+    }
+
+    #[test]
+    fn sends_start_data_and_stop_bits() {
+        // 50 baud at 400 samples a second is 8 samples a bit
+        let mut line = Framing::BAUD_50.transmit([0x05].into_iter(), 400).unwrap();
+        let mut bits = [false; 8];
+        for bit in bits.iter_mut() {
+            let samples = [(); 8].map(|_| line.next().unwrap());
+            assert!(samples.iter().all(|&s| s == samples[0]));
+            *bit = samples[0];
+        }
+
+        assert_eq!(bits, [false, true, false, true, false, false, true, true]);
+        assert_eq!(line.next(), None);
+    }
+
+    #[test]
+    fn keeps_time_over_a_long_message() {
+        let samples = Framing::BAUD_45.transmit(codes(), RATE).unwrap().count();
+        let exact = 200.0 * 7.5 * RATE as f32 / 45.45;
+
+        assert!((samples as f32 - exact).abs() < 1.0);
+    }
+
+    #[test]
+    fn either_polarity() {
+        let framing = Framing::BAUD_50.with_polarity(Polarity::MarkLow);
+        let line = || framing.transmit(codes(), RATE).unwrap();
+
+        assert!(framing.receive(line(), RATE).unwrap().map(Result::unwrap).eq(codes()));
+        assert!(Framing::BAUD_50.receive(line(), RATE).unwrap().any(|c| c.is_err()));
+    }
+
+    #[test]
+    fn ignores_short_glitches() {
+        let framing = Framing::BAUD_50;
+        let glitch = [true; 40].into_iter().chain([false; 20]).chain([true; 400]);
+        let line = glitch.chain(framing.transmit([0x1F, 0x03].into_iter(), RATE).unwrap());
+
+        assert!(framing.receive(line, RATE).unwrap().map(Result::unwrap).eq([0x1F, 0x03]));
+    }
+
+    #[test]
+    fn reports_a_missing_stop_bit() {
+        let framing = Framing::BAUD_50;
+        // 160 samples a bit; hold the line at space through the first stop bit
+        let line = framing.transmit([0x03].into_iter(), RATE).unwrap()
+            .enumerate()
+            .map(|(i, level)| level && !(960..1120).contains(&i))
+            .chain([true; 400])
+            .chain(framing.transmit([0x10].into_iter(), RATE).unwrap());
+
+        let mut received = framing.receive(line, RATE).unwrap();
+        assert_eq!(received.next(), Some(Err(FramingError { start: 0, code: 0x03 })));
+        assert_eq!(received.next(), Some(Ok(0x10)));
+        assert_eq!(received.next(), None);
+    }
+    #[test]
+    fn refuses_rates_it_cannot_time() {
+        for baud in [0.0, -50.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(Framing::new(baud), Err(RateError::InvalidBaud { .. })), "{baud}");
+        }
+        assert_eq!(Framing::new(75.0).map(|framing| framing.baud()), Ok(75.0));
+
+        assert!(matches!(Framing::BAUD_50.transmit(codes(), 0), Err(RateError::ZeroSampleRate)));
+        assert!(matches!(Framing::BAUD_50.receive([true; 10].into_iter(), 0), Err(RateError::ZeroSampleRate)));
+    }
+
+    #[test]
+    fn ends_when_characters_round_to_no_samples() {
+        // A character at 50 baud is a sixth of a sample at one sample a second
+        let samples = Framing::BAUD_50.transmit(codes(), 1).unwrap().count();
+
+        assert!((samples as f32 - 200.0 * 8.0 / 50.0).abs() < 1.0);
+    }
+}
@@ -1,108 +1,136 @@
+//! The ITA2 teleprinter code, with impulse 1 in the least significant bit.
+//!
+//! Both directions share one table giving what each code means in each shift
+//! of a `CharacterSet`. Codes which mean the same in every shift (space,
+//! carriage return, line feed and the shifts themselves) never need a shift.
+
+use core::iter::{once, Chain, Flatten, Once};
+use core::option;
+
+use Event::{Char, ShiftChanged};
+
+mod bletchley;
+mod charset;
+mod normalize;
+
+pub use bletchley::{from_bletchley, to_bletchley, Bletchley, FromBletchley, NotBletchley, BLETCHLEY_ALPHABET};
+pub use charset::CharacterSet;
+pub use normalize::{Lossy, Normalizer, Unrepresentable};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Shift {
+    #[default]
+    Letters,
+    Figures,
+    /// The third shift of `CharacterSet::Mtk2`
+    Cyrillic,
+}
+
+/// What a code means to a receiving teleprinter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// A printing character, space included
+    Char(char),
+    /// The all-spaces code, which a teleprinter ignores
+    Null,
+    Bell,
+    /// Asks the far end to send its answerback
+    WhoAreYou,
+    CarriageReturn,
+    LineFeed,
+    /// A shift code was received; the receiver is now in the given shift
+    ShiftChanged(Shift),
+    /// A code with no meaning in the character set's current shift
+    Unassigned,
+}
+
+impl Event {
+    /// The event as a character, with the controls as their ASCII equivalents.
+    /// Shifts and unassigned codes have no character.
+    pub fn as_char(self) -> Option<char> {
+        match self {
+            Event::Char(c) => Some(c),
+            Event::Null => Some('\0'),
+            Event::Bell => Some('\u{0007}'),
+            Event::WhoAreYou => Some('\u{0005}'),
+            Event::CarriageReturn => Some('\r'),
+            Event::LineFeed => Some('\n'),
+            Event::ShiftChanged(_) | Event::Unassigned => None,
+        }
+    }
+
+    /// The event a character stands for, the inverse of `as_char`.
+    pub fn from_char(c: char) -> Event {
+        match c {
+            '\0' => Event::Null,
+            '\u{0007}' => Event::Bell,
+            '\u{0005}' => Event::WhoAreYou,
+            '\r' => Event::CarriageReturn,
+            '\n' => Event::LineFeed,
+            c => Event::Char(c),
+        }
+    }
+}
+
 pub struct Decoder<T>
 where
     T: Iterator<Item = u8>,
 {
-    figure_shift: bool,
+    shift: Shift,
+    unshift_on_space: bool,
+    character_set: CharacterSet,
     source: T
 }
 
+impl <T : Iterator<Item = u8>> Decoder<T> {
+    /// A decoder starting in letters, as a teleprinter does after a reset.
+    pub fn new(source: T) -> Self {
+        Self::with_shift(source, Shift::Letters)
+    }
+
+    /// A decoder starting in `shift`, for traffic picked up part way through.
+    pub fn with_shift(source: T, shift: Shift) -> Self {
+        Self {
+            shift,
+            unshift_on_space: false,
+            character_set: CharacterSet::default(),
+            source
+        }
+    }
+
+    pub fn with_character_set(mut self, character_set: CharacterSet) -> Self {
+        self.character_set = character_set;
+        self
+    }
+
+    /// Returns to letters on every space, as many US teletypes do. The space
+    /// is still decoded as `Event::Char(' ')`, with no `ShiftChanged`.
+    pub fn with_unshift_on_space(mut self, unshift_on_space: bool) -> Self {
+        self.unshift_on_space = unshift_on_space;
+        self
+    }
+
+    /// The shift the next code will be read in.
+    pub fn shift(&self) -> Shift {
+        self.shift
+    }
+}
+
+/// Yields one event per code. Only the low five bits of each code are used.
 impl <T : Iterator<Item = u8>> Iterator for Decoder<T> {
-    type Item = char;
+    type Item = Event;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(v) = self.source.next() {
-            if self.figure_shift {
-                match v {
-                    0x0 => Some('\0'),
-                    0x1 => Some('E'),
-                    0x2 => Some('\n'),
-                    0x3 => Some('A'),
-                    0x4 => Some(' '),
-                    0x5 => Some('S'),
-                    0x6 => Some('I'),
-                    0x7 => Some('U'),
-                    0x8 => Some('\r'),
-                    0x9 => Some('D'),
-                    0xA => Some('R'),
-                    0xB => Some('J'),
-                    0xC => Some('N'),
-                    0xD => Some('F'),
-                    0xE => Some('C'),
-                    0xF => Some('K'),
-                    0x10 => Some('T'),
-                    0x11 => Some('Z'),
-                    0x12 => Some('L'),
-                    0x13 => Some('W'),
-                    0x14 => Some('H'),
-                    0x15 => Some('Y'),
-                    0x16 => Some('P'),
-                    0x17 => Some('Q'),
-                    0x18 => Some('O'),
-                    0x19 => Some('B'),
-                    0x1A => Some('G'),
-                    0x1B => {
-                        self.figure_shift = true;
-
-                        self.next()
-                    }
-                    0x1C => Some('M'),
-                    0x1D => Some('X'),
-                    0x1E => Some('V'),
-                    0x1F => {
-                        self.figure_shift = false;
+        let code = self.source.next()?;
+        let event = self.character_set.table(self.shift)[(code & 0x1F) as usize];
 
-                        self.next()
-                    }
-                    _ => self.next()
-                }
-            } else {
-                match v {
-                    0x0 => Some('\0'),
-                    0x1 => Some('3'),
-                    0x2 => Some('\n'),
-                    0x3 => Some('-'),
-                    0x4 => Some(' '),
-                    0x5 => Some('\''),
-                    0x6 => Some('8'),
-                    0x7 => Some('7'),
-                    0x8 => Some('\r'),
-                    0x9 => Some('\u{0005}'), // WHO ARE YOU
-                    0xA => Some('4'),
-                    0xB => Some('\u{0007}'), // BELL
-                    0xC => Some(','),
-                    0xD => Some('!'),
-                    0xE => Some(':'),
-                    0xF => Some('('),
-                    0x10 => Some('5'),
-                    0x11 => Some('+'),
-                    0x12 => Some(')'),
-                    0x13 => Some('2'),
-                    0x14 => Some('£'),
-                    0x15 => Some('6'),
-                    0x16 => Some('0'),
-                    0x17 => Some('1'),
-                    0x18 => Some('9'),
-                    0x19 => Some('?'),
-                    0x1A => Some('&'),
-                    0x1B => {
-                        self.figure_shift = true;
-
-                        self.next()
-                    }
-                    0x1C => Some('.'),
-                    0x1D => Some('/'),
-                    0x1E => Some('='),
-                    0x1F => {
-                        self.figure_shift = false;
-
-                        self.next()
-                    }
-                    _ => self.next()
-                }
-            }
-        } else {
-            None
+        match event {
+            ShiftChanged(shift) => self.shift = shift,
+            Char(' ') if self.unshift_on_space => self.shift = Shift::Letters,
+            _ => {}
         }
+
+        Some(event)
     }
 }
 
@@ -110,21 +138,46 @@ pub struct Encoder<T>
     where
         T: Iterator<Item = char>,
 {
-    figure_shift: bool,
+    shift: Shift,
+    /// Whether the receiver can be trusted to still be in `shift`
+    shift_known: bool,
+    minimal_shifts: bool,
+    character_set: CharacterSet,
     source: T,
 }
 
+/// The codes of an `Encoder`, shifts included, one per step of the machine.
+pub type Codes<T> = Flatten<Encoder<T>>;
+
 impl <T: Iterator<Item = char>> Encoder<T> {
     pub fn new(source: T) -> Self {
         Self {
-            figure_shift: false,
+            shift: Shift::Letters,
+            shift_known: true,
+            minimal_shifts: true,
+            character_set: CharacterSet::default(),
             source
         }
     }
-}
 
-const FS : u8 = 0x1B;
-const LS : u8 = 0x1F;
+    pub fn with_character_set(mut self, character_set: CharacterSet) -> Self {
+        self.character_set = character_set;
+        self
+    }
+
+    /// With `minimal_shifts` off, the shift is sent again before the first
+    /// printing character after every space, carriage return or line feed,
+    /// keeping receivers which unshift on space in step. On by default.
+    pub fn with_minimal_shifts(mut self, minimal_shifts: bool) -> Self {
+        self.minimal_shifts = minimal_shifts;
+        self
+    }
+
+    /// A flat stream of 5-bit codes with the shift codes inline.
+    pub fn codes(self) -> Codes<T> {
+        self.flatten()
+    }
+}
 
 pub enum EncoderOut {
     Single(u8),
@@ -143,440 +196,183 @@ impl From<[u8;2]> for EncoderOut {
     }
 }
 
+/// Yields the shift code (if any) followed by the character code, so an
+/// `Encoder` can be `flatten`ed into a plain stream of 5-bit codes.
+impl IntoIterator for EncoderOut {
+    type Item = u8;
+    type IntoIter = Chain<option::IntoIter<u8>, Once<u8>>;
 
+    fn into_iter(self) -> Self::IntoIter {
+        match self {
+            EncoderOut::Single(c) => None.into_iter().chain(once(c)),
+            EncoderOut::ShiftAndChar(shift, c) => Some(shift).into_iter().chain(once(c)),
+        }
+    }
+}
+
+/// Characters with no code in any shift are skipped; pass text through a
+/// `Normalizer` first to fold, replace or report them. Control characters are
+/// read as in `Event::from_char`.
 impl<T: Iterator<Item = char>> Iterator for Encoder<T> {
     type Item = EncoderOut;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(v) = self.source.next() {
-            match v {
-                // Doesn't care
-                '\0' => Some(0x0.into()),
-                '\n' => Some(0x2.into()),
-                ' ' => Some(0x4.into()),
-                '\r' => Some(0x8.into()),
-                // Letter Shift
-                'E' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x1].into()
-                    } else {
-                        0x1.into()
-                    }
-                }),
-                'A' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x3].into()
-                    } else {
-                        0x3.into()
-                    }
-                }),
-                'S' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x5].into()
-                    } else {
-                        0x5.into()
-                    }
-                }),
-                'I' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x6].into()
-                    } else {
-                        0x6.into()
-                    }
-                }),
-                'U' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x7].into()
-                    } else {
-                        0x7.into()
-                    }
-                }),
-                'D' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x9].into()
-                    } else {
-                        0x9.into()
-                    }
-                }),
-                'R' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0xA].into()
-                    } else {
-                        0xA.into()
-                    }
-                }),
-                'J' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0xB].into()
-                    } else {
-                        0xB.into()
-                    }
-                }),
-                'N' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0xC].into()
-                    } else {
-                        0xC.into()
-                    }
-                }),
-                'F' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0xD].into()
-                    } else {
-                        0xD.into()
-                    }
-                }),
-                'C' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0xE].into()
-                    } else {
-                        0xE.into()
-                    }
-                }),
-                'K' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0xF].into()
-                    } else {
-                        0xF.into()
-                    }
-                }),
-                'T' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x10].into()
-                    } else {
-                        0x10.into()
-                    }
-                }),
-                'Z' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x11].into()
-                    } else {
-                        0x11.into()
-                    }
-                }),
-                'L' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x12].into()
-                    } else {
-                        0x12.into()
-                    }
-                }),
-                'W' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x13].into()
-                    } else {
-                        0x13.into()
-                    }
-                }),
-                'H' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x14].into()
-                    } else {
-                        0x14.into()
-                    }
-                }),
-                'Y' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x15].into()
-                    } else {
-                        0x15.into()
-                    }
-                }),
-                'P' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x16].into()
-                    } else {
-                        0x16.into()
-                    }
-                }),
-                'Q' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x17].into()
-                    } else {
-                        0x17.into()
-                    }
-                }),
-                'O' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x18].into()
-                    } else {
-                        0x18.into()
-                    }
-                }),
-                'B' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x19].into()
-                    } else {
-                        0x19.into()
-                    }
-                }),
-                'G' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x1A].into()
-                    } else {
-                        0x1A.into()
-                    }
-                }),
-                'M' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x1C].into()
-                    } else {
-                        0x1C.into()
-                    }
-                }),
-                'X' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x1D].into()
-                    } else {
-                        0x1D.into()
-                    }
-                }),
-                'V' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = false;
-                        [LS, 0x1E].into()
-                    } else {
-                        0x1E.into()
-                    }
-                }),
-                // FS
-                '3' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x1].into()
-                    } else {
-                        0x1.into()
-                    }
-                }),
-                '-' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x3].into()
-                    } else {
-                        0x3.into()
-                    }
-                }),
-                '\'' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x5].into()
-                    } else {
-                        0x5.into()
-                    }
-                }),
-                '8' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x6].into()
-                    } else {
-                        0x6.into()
-                    }
-                }),
-                '7' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x7].into()
-                    } else {
-                        0x7.into()
-                    }
-                }),
-                '\u{0005}' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x9].into()
-                    } else {
-                        0x9.into()
-                    }
-                }), // WHO ARE YOU
-                '4' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0xA].into()
-                    } else {
-                        0xA.into()
-                    }
-                }),
-                '\u{0007}' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0xB].into()
-                    } else {
-                        0xB.into()
-                    }
-                }), // BELL
-                ',' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0xC].into()
-                    } else {
-                        0xC.into()
-                    }
-                }),
-                '!' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0xD].into()
-                    } else {
-                        0xD.into()
-                    }
-                }),
-                ':' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0xE].into()
-                    } else {
-                        0xE.into()
-                    }
-                }),
-                '(' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0xF].into()
-                    } else {
-                        0xF.into()
-                    }
-                }),
-                '5' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x10].into()
-                    } else {
-                        0x10.into()
-                    }
-                }),
-                '+' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x11].into()
-                    } else {
-                        0x11.into()
-                    }
-                }),
-                ')' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x12].into()
-                    } else {
-                        0x12.into()
-                    }
-                }),
-                '2' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x13].into()
-                    } else {
-                        0x13.into()
-                    }
-                }),
-                '£' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x14].into()
-                    } else {
-                        0x14.into()
-                    }
-                }),
-                '6' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x15].into()
-                    } else {
-                        0x15.into()
-                    }
-                }),
-                '0' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x16].into()
-                    } else {
-                        0x16.into()
-                    }
-                }),
-                '1' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x17].into()
-                    } else {
-                        0x17.into()
-                    }
-                }),
-                '9' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x18].into()
-                    } else {
-                        0x18.into()
-                    }
-                }),
-                '?' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x19].into()
-                    } else {
-                        0x19.into()
-                    }
-                }),
-                '&' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x1A].into()
-                    } else {
-                        0x1A.into()
-                    }
-                }),
-                '.' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x1C].into()
-                    } else {
-                        0x1C.into()
-                    }
-                }),
-                '/' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x1D].into()
-                    } else {
-                        0x1D.into()
-                    }
-                }),
-                '=' => Some({
-                    if self.figure_shift {
-                        self.figure_shift = true;
-                        [FS, 0x1E].into()
-                    } else {
-                        0x1E.into()
+        loop {
+            let event = Event::from_char(self.source.next()?);
+
+            let set = self.character_set;
+            let neutral = matches!(event, Char(' ') | Event::CarriageReturn | Event::LineFeed);
+
+            if let Some(code) = set.code(self.shift, event) {
+                if neutral && !self.minimal_shifts {
+                    self.shift_known = false;
+                }
+                if neutral || event == Event::Null || self.shift_known {
+                    return Some(code.into());
+                }
+            }
+
+            // The current shift comes first, for when it only needs sending again
+            let current = Some(self.shift).into_iter();
+            for other in current.chain(set.shifts().iter().copied()) {
+                if let (Some(code), Some(shift_code)) = (set.code(other, event), set.code(self.shift, ShiftChanged(other))) {
+                    self.shift = other;
+                    self.shift_known = true;
+                    return Some([shift_code, code].into());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Event::{Bell, CarriageReturn, LineFeed, Null, WhoAreYou};
+
+    const FS: u8 = 0x1B;
+    const LS: u8 = 0x1F;
+    const TO_FIGURES: Event = ShiftChanged(Shift::Figures);
+    const TO_LETTERS: Event = ShiftChanged(Shift::Letters);
+
+    const SETS: [CharacterSet; 4] = [CharacterSet::Ita2, CharacterSet::Uk, CharacterSet::UsTty, CharacterSet::Mtk2];
+
+    /// A character found only in `shift`, to put the encoder in that shift.
+    fn only_in(set: CharacterSet, shift: Shift) -> char {
+        set.table(shift).iter()
+            .copied()
+            .find(|&e| matches!(e, Char(_)) && set.shifts().iter().all(|&s| s == shift || set.code(s, e).is_none()))
+            .and_then(Event::as_char)
+            .unwrap()
+    }
+
+    #[test]
+    fn every_character_round_trips_from_every_shift() {
+        for set in SETS {
+            for &shift in set.shifts() {
+                let encodable = set.table(shift).iter()
+                    .copied()
+                    .filter(|e| !matches!(e, ShiftChanged(_) | Event::Unassigned));
+
+                for event in encodable {
+                    let c = event.as_char().unwrap();
+
+                    for &from in set.shifts() {
+                        let lead = only_in(set, from);
+                        let mut codes = Encoder::new([lead, c].into_iter()).with_character_set(set).codes();
+                        let mut decoded = Decoder::new(&mut codes).with_character_set(set).filter_map(Event::as_char);
+
+                        assert_eq!(decoded.next(), Some(lead), "{set:?}: {event:?} after {from:?}");
+                        assert_eq!(decoded.next(), Some(c), "{set:?}: {event:?} after {from:?}");
+                        assert_eq!(decoded.next(), None, "{set:?}: {event:?} after {from:?}");
                     }
-                }),
-                _ => self.next()
+                }
             }
-        } else {
-            None
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn variants_differ_in_the_figures() {
+        let figures = |set: CharacterSet| {
+            let mut decoder = Decoder::with_shift([0x14, 0x05, 0x09, 0x0D].into_iter(), Shift::Figures)
+                .with_character_set(set);
+            [(); 4].map(|_| decoder.next().unwrap())
+        };
+
+        assert_eq!(figures(CharacterSet::Ita2), [Event::Unassigned, Char('\''), WhoAreYou, Event::Unassigned]);
+        assert_eq!(figures(CharacterSet::Uk), [Char('£'), Char('\''), WhoAreYou, Char('!')]);
+        assert_eq!(figures(CharacterSet::UsTty), [Char('#'), Bell, Char('$'), Char('!')]);
+        assert_eq!(figures(CharacterSet::Mtk2), [Char('Щ'), Char('\''), WhoAreYou, Char('Э')]);
+    }
+
+    #[test]
+    fn mtk2_shifts_to_cyrillic_on_the_all_spaces_code() {
+        let codes = Encoder::new("ДА DA".chars()).with_character_set(CharacterSet::Mtk2).codes();
+
+        assert!(codes.eq([0x00, 0x09, 0x03, 0x04, LS, 0x09, 0x03]));
+    }
+
+    #[test]
+    fn shifts_only_when_needed() {
+        let mut codes = [0; 8];
+        let mut len = 0;
+        for (out, code) in codes.iter_mut().zip(Encoder::new("A1 2B".chars()).codes()) {
+            *out = code;
+            len += 1;
+        }
+
+        assert_eq!(&codes[..len], &[0x03, FS, 0x17, 0x04, 0x13, LS, 0x19]);
+    }
+
+    #[test]
+    fn can_repeat_shifts_after_spaces() {
+        let text = "1 2 A B\r\n3";
+
+        assert!(Encoder::new(text.chars()).codes().eq(
+            [FS, 0x17, 0x04, 0x13, 0x04, LS, 0x03, 0x04, 0x19, 0x08, 0x02, FS, 0x01]
+        ));
+        assert!(Encoder::new(text.chars()).with_minimal_shifts(false).codes().eq(
+            [FS, 0x17, 0x04, FS, 0x13, 0x04, LS, 0x03, 0x04, LS, 0x19, 0x08, 0x02, FS, 0x01]
+        ));
+
+        let sent = Encoder::new(text.chars()).with_minimal_shifts(false).codes();
+        assert!(Decoder::new(sent).with_unshift_on_space(true).filter_map(Event::as_char).eq(text.chars()));
+    }
+
+    #[test]
+    fn decodes_controls_as_events() {
+        let codes = [0x00, FS, 0x0B, 0x09, 0x08, 0x02, LS, 0x04];
+        let events = [
+            Null, TO_FIGURES, Bell, WhoAreYou, CarriageReturn, LineFeed, TO_LETTERS, Char(' '),
+        ];
+
+        assert!(Decoder::new(codes.into_iter()).eq(events));
+    }
+
+    #[test]
+    fn starts_in_the_given_shift() {
+        let mut decoder = Decoder::with_shift([0x17, LS, 0x17].into_iter(), Shift::Figures);
+
+        assert_eq!(decoder.shift(), Shift::Figures);
+        assert_eq!(decoder.next(), Some(Char('1')));
+        assert_eq!(decoder.next(), Some(TO_LETTERS));
+        assert_eq!(decoder.shift(), Shift::Letters);
+        assert_eq!(decoder.next(), Some(Char('Q')));
+    }
+
+    #[test]
+    fn space_can_return_to_letters() {
+        let codes = [FS, 0x17, 0x04, 0x17];
+
+        assert!(Decoder::new(codes.into_iter()).filter_map(Event::as_char).eq("1 1".chars()));
+
+        let mut decoder = Decoder::new(codes.into_iter()).with_unshift_on_space(true);
+        assert!(decoder.by_ref().filter_map(Event::as_char).eq("1 Q".chars()));
+        assert_eq!(decoder.shift(), Shift::Letters);
+    }
+
+    #[test]
+    fn skips_characters_without_a_code() {
+        assert!(Encoder::new("a~B".chars()).codes().eq([0x19]));
+    }
+}
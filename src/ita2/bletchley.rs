@@ -0,0 +1,120 @@
+//! The notation Bletchley Park wrote teleprinter codes in: the letter for
+//! each letter code, and `/`, `9`, `3`, `4`, `8` and `5` for null, space,
+//! line feed, carriage return, letter shift and figure shift.
+
+use core::fmt;
+
+/// The Bletchley alphabet, indexed by code with impulse 1 in the least
+/// significant bit.
+pub const BLETCHLEY_ALPHABET: [char; 32] = [
+    '/', 'E', '3', 'A', '9', 'S', 'I', 'U',
+    '4', 'D', 'R', 'J', 'N', 'F', 'C', 'K',
+    'T', 'Z', 'L', 'W', 'H', 'Y', 'P', 'Q',
+    'O', 'B', 'G', '5', 'M', 'X', 'V', '8',
+];
+
+/// The Bletchley character for a code. Only the low five bits are used.
+pub fn to_bletchley(code: u8) -> char {
+    BLETCHLEY_ALPHABET[(code & 0x1F) as usize]
+}
+
+/// The code for a Bletchley character, taking letters in either case.
+pub fn from_bletchley(c: char) -> Option<u8> {
+    let c = c.to_ascii_uppercase();
+    BLETCHLEY_ALPHABET.iter().position(|&b| b == c).map(|code| code as u8)
+}
+
+/// Displays a run of codes in Bletchley notation.
+#[derive(Clone, Copy, Debug)]
+pub struct Bletchley<'a>(pub &'a [u8]);
+
+impl fmt::Display for Bletchley<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &code in self.0 {
+            fmt::Write::write_char(f, to_bletchley(code))?;
+        }
+        Ok(())
+    }
+}
+
+/// A character in a Bletchley string which is not in the alphabet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NotBletchley {
+    /// The character's index in the string, counting characters from 0
+    pub index: usize,
+    pub found: char,
+}
+
+impl fmt::Display for NotBletchley {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at {} is not in the Bletchley alphabet", self.found, self.index)
+    }
+}
+
+/// Reads codes from Bletchley notation, skipping whitespace.
+pub struct FromBletchley<T>
+where
+    T: Iterator<Item = char>,
+{
+    source: T,
+    index: usize,
+}
+
+impl<T: Iterator<Item = char>> FromBletchley<T> {
+    pub fn new(source: T) -> Self {
+        FromBletchley { source, index: 0 }
+    }
+}
+
+impl<T: Iterator<Item = char>> Iterator for FromBletchley<T> {
+    type Item = Result<u8, NotBletchley>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let c = self.source.next()?;
+            let index = self.index;
+            self.index += 1;
+
+            if !c.is_whitespace() {
+                return Some(from_bletchley(c).ok_or(NotBletchley { index, found: c }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn every_code_round_trips() {
+        for code in 0..32 {
+            assert_eq!(from_bletchley(to_bletchley(code)), Some(code));
+        }
+    }
+
+    #[test]
+    fn figures_stand_for_the_non_letter_codes() {
+        let codes = [0x00, 0x04, 0x02, 0x08, 0x1F, 0x1B];
+        assert_eq!(codes.map(to_bletchley), ['/', '9', '3', '4', '8', '5']);
+    }
+
+    #[test]
+    fn reads_and_writes_strings() {
+        let mut codes = [0; 14];
+        for (out, code) in codes.iter_mut().zip(FromBletchley::new("hello5n98 WORLD".chars())) {
+            *out = code.unwrap();
+        }
+
+        assert_eq!(Bletchley(&codes).to_string(), "HELLO5N98WORLD");
+    }
+
+    #[test]
+    fn reports_characters_outside_the_alphabet() {
+        let mut codes = FromBletchley::new("HT !".chars());
+
+        assert_eq!(codes.nth(2), Some(Err(NotBletchley { index: 3, found: '!' })));
+    }
+}
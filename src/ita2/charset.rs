@@ -0,0 +1,103 @@
+//! The national variants of the code. They share the letters, and differ in
+//! the figures the ITA2 standard left for national use and in the controls.
+
+use super::Event::{self, Bell, CarriageReturn, Char, LineFeed, Null, ShiftChanged, Unassigned, WhoAreYou};
+use super::Shift;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CharacterSet {
+    /// The international alphabet, with the figures on F, G and H unassigned
+    Ita2,
+    /// The British layout, with `!`, `&` and `£` on F, G and H
+    #[default]
+    Uk,
+    /// The US teletype layout, with bell on S, `$` in place of who are you
+    /// and `"`, `#` and `;` on Z, H and V
+    UsTty,
+    /// The Soviet MTK-2, with a third shift of Cyrillic letters selected by
+    /// the all-spaces code. It has no Ч, which was sent as the figure 4.
+    Mtk2,
+}
+
+const TO_FIGURES: Event = ShiftChanged(Shift::Figures);
+const TO_LETTERS: Event = ShiftChanged(Shift::Letters);
+const TO_CYRILLIC: Event = ShiftChanged(Shift::Cyrillic);
+
+/// The letter shift, indexed by code.
+const LETTERS: [Event; 32] = [
+    Null, Char('E'), LineFeed, Char('A'), Char(' '), Char('S'), Char('I'), Char('U'),
+    CarriageReturn, Char('D'), Char('R'), Char('J'), Char('N'), Char('F'), Char('C'), Char('K'),
+    Char('T'), Char('Z'), Char('L'), Char('W'), Char('H'), Char('Y'), Char('P'), Char('Q'),
+    Char('O'), Char('B'), Char('G'), TO_FIGURES, Char('M'), Char('X'), Char('V'), TO_LETTERS,
+];
+
+const ITA2_FIGURES: [Event; 32] = [
+    Null, Char('3'), LineFeed, Char('-'), Char(' '), Char('\''), Char('8'), Char('7'),
+    CarriageReturn, WhoAreYou, Char('4'), Bell, Char(','), Unassigned, Char(':'), Char('('),
+    Char('5'), Char('+'), Char(')'), Char('2'), Unassigned, Char('6'), Char('0'), Char('1'),
+    Char('9'), Char('?'), Unassigned, TO_FIGURES, Char('.'), Char('/'), Char('='), TO_LETTERS,
+];
+
+const UK_FIGURES: [Event; 32] = [
+    Null, Char('3'), LineFeed, Char('-'), Char(' '), Char('\''), Char('8'), Char('7'),
+    CarriageReturn, WhoAreYou, Char('4'), Bell, Char(','), Char('!'), Char(':'), Char('('),
+    Char('5'), Char('+'), Char(')'), Char('2'), Char('£'), Char('6'), Char('0'), Char('1'),
+    Char('9'), Char('?'), Char('&'), TO_FIGURES, Char('.'), Char('/'), Char('='), TO_LETTERS,
+];
+
+const US_TTY_FIGURES: [Event; 32] = [
+    Null, Char('3'), LineFeed, Char('-'), Char(' '), Bell, Char('8'), Char('7'),
+    CarriageReturn, Char('$'), Char('4'), Char('\''), Char(','), Char('!'), Char(':'), Char('('),
+    Char('5'), Char('"'), Char(')'), Char('2'), Char('#'), Char('6'), Char('0'), Char('1'),
+    Char('9'), Char('?'), Char('&'), TO_FIGURES, Char('.'), Char('/'), Char(';'), TO_LETTERS,
+];
+
+const MTK2_LETTERS: [Event; 32] = [
+    TO_CYRILLIC, Char('E'), LineFeed, Char('A'), Char(' '), Char('S'), Char('I'), Char('U'),
+    CarriageReturn, Char('D'), Char('R'), Char('J'), Char('N'), Char('F'), Char('C'), Char('K'),
+    Char('T'), Char('Z'), Char('L'), Char('W'), Char('H'), Char('Y'), Char('P'), Char('Q'),
+    Char('O'), Char('B'), Char('G'), TO_FIGURES, Char('M'), Char('X'), Char('V'), TO_LETTERS,
+];
+
+const MTK2_CYRILLIC: [Event; 32] = [
+    TO_CYRILLIC, Char('Е'), LineFeed, Char('А'), Char(' '), Char('С'), Char('И'), Char('У'),
+    CarriageReturn, Char('Д'), Char('Р'), Char('Й'), Char('Н'), Char('Ф'), Char('Ц'), Char('К'),
+    Char('Т'), Char('З'), Char('Л'), Char('В'), Char('Х'), Char('Ы'), Char('П'), Char('Я'),
+    Char('О'), Char('Б'), Char('Г'), TO_FIGURES, Char('М'), Char('Ь'), Char('Ж'), TO_LETTERS,
+];
+
+const MTK2_FIGURES: [Event; 32] = [
+    TO_CYRILLIC, Char('3'), LineFeed, Char('-'), Char(' '), Char('\''), Char('8'), Char('7'),
+    CarriageReturn, WhoAreYou, Char('4'), Char('Ю'), Char(','), Char('Э'), Char(':'), Char('('),
+    Char('5'), Char('+'), Char(')'), Char('2'), Char('Щ'), Char('6'), Char('0'), Char('1'),
+    Char('9'), Char('?'), Char('Ш'), TO_FIGURES, Char('.'), Char('/'), Char('='), TO_LETTERS,
+];
+
+impl CharacterSet {
+    /// What each code means in `shift`, indexed by code. Sets without a
+    /// Cyrillic shift read it as letters.
+    pub fn table(self, shift: Shift) -> &'static [Event; 32] {
+        match (self, shift) {
+            (CharacterSet::Mtk2, Shift::Letters) => &MTK2_LETTERS,
+            (CharacterSet::Mtk2, Shift::Cyrillic) => &MTK2_CYRILLIC,
+            (CharacterSet::Mtk2, Shift::Figures) => &MTK2_FIGURES,
+            (_, Shift::Letters | Shift::Cyrillic) => &LETTERS,
+            (CharacterSet::Ita2, Shift::Figures) => &ITA2_FIGURES,
+            (CharacterSet::Uk, Shift::Figures) => &UK_FIGURES,
+            (CharacterSet::UsTty, Shift::Figures) => &US_TTY_FIGURES,
+        }
+    }
+
+    /// The shifts this set has, letters first.
+    pub fn shifts(self) -> &'static [Shift] {
+        match self {
+            CharacterSet::Mtk2 => &[Shift::Letters, Shift::Figures, Shift::Cyrillic],
+            _ => &[Shift::Letters, Shift::Figures],
+        }
+    }
+
+    /// The code for `event` in `shift`, if it has one there.
+    pub fn code(self, shift: Shift, event: Event) -> Option<u8> {
+        self.table(shift).iter().position(|&e| e == event).map(|code| code as u8)
+    }
+}
@@ -0,0 +1,205 @@
+//! Brings arbitrary text down to what a character set can send, before it
+//! reaches an `Encoder`.
+//!
+//! Each character is tried as it is, then upper-cased, then transliterated
+//! (`É` → `E`, `Ü` → `UE`, `“` → `'`), then spelled out (`@` → `AT`), taking
+//! the first which the character set can send. German umlauts follow the
+//! teleprinter convention of an added `E`.
+
+use core::fmt;
+use core::iter::FilterMap;
+use core::str::Chars;
+
+use super::{CharacterSet, Event};
+
+/// A character that could not be brought into the character set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Unrepresentable {
+    /// The character's index in the text, counting characters from 0
+    pub index: usize,
+    pub found: char,
+}
+
+impl fmt::Display for Unrepresentable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at {} cannot be sent", self.found, self.index)
+    }
+}
+
+fn transliteration(c: char) -> Option<&'static str> {
+    Some(match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Å' => "A",
+        'Ä' | 'Æ' => "AE",
+        'Ç' => "C",
+        'È' | 'É' | 'Ê' | 'Ë' => "E",
+        'Ì' | 'Í' | 'Î' | 'Ï' => "I",
+        'Ñ' => "N",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ø' => "O",
+        'Ö' | 'Œ' => "OE",
+        'Ù' | 'Ú' | 'Û' => "U",
+        'Ü' => "UE",
+        'Ý' | 'Ÿ' => "Y",
+        'ß' => "SS",
+        '‘' | '’' | '`' | '´' | '"' | '“' | '”' | '„' => "'",
+        '–' | '—' | '_' => "-",
+        '…' => "...",
+        '[' | '{' | '<' => "(",
+        ']' | '}' | '>' => ")",
+        ';' => ",",
+        '\t' => " ",
+        _ => return None,
+    })
+}
+
+fn spelling(c: char) -> Option<&'static str> {
+    Some(match c {
+        '@' => "AT",
+        '&' => "AND",
+        '%' => "PERCENT",
+        '+' => "PLUS",
+        '=' => "EQUALS",
+        '#' => "NUMBER",
+        '$' => "DOLLARS",
+        '£' => "POUNDS",
+        _ => return None,
+    })
+}
+
+pub struct Normalizer<T>
+where
+    T: Iterator<Item = char>,
+{
+    character_set: CharacterSet,
+    fold_case: bool,
+    transliterate: bool,
+    spell_out: bool,
+    source: T,
+    index: usize,
+    pending: Chars<'static>,
+}
+
+impl<T: Iterator<Item = char>> Normalizer<T> {
+    /// A normalizer for `character_set` with every stage turned on.
+    pub fn new(source: T, character_set: CharacterSet) -> Self {
+        Self {
+            character_set,
+            fold_case: true,
+            transliterate: true,
+            spell_out: true,
+            source,
+            index: 0,
+            pending: "".chars(),
+        }
+    }
+
+    pub fn with_fold_case(mut self, fold_case: bool) -> Self {
+        self.fold_case = fold_case;
+        self
+    }
+
+    pub fn with_transliteration(mut self, transliterate: bool) -> Self {
+        self.transliterate = transliterate;
+        self
+    }
+
+    pub fn with_spelling_out(mut self, spell_out: bool) -> Self {
+        self.spell_out = spell_out;
+        self
+    }
+
+    /// Drops characters which cannot be sent rather than reporting them.
+    pub fn lossy(self) -> Lossy<T> {
+        self.filter_map(Result::ok as fn(_) -> _)
+    }
+
+    fn sendable(&self, c: char) -> bool {
+        let event = Event::from_char(c);
+        self.character_set.shifts().iter().any(|&shift| self.character_set.code(shift, event).is_some())
+    }
+
+    fn all_sendable(&self, text: &str) -> bool {
+        text.chars().all(|c| self.sendable(c))
+    }
+}
+
+pub type Lossy<T> = FilterMap<Normalizer<T>, fn(Result<char, Unrepresentable>) -> Option<char>>;
+
+impl<T: Iterator<Item = char>> Iterator for Normalizer<T> {
+    type Item = Result<char, Unrepresentable>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(c) = self.pending.next() {
+            return Some(Ok(c));
+        }
+
+        let c = self.source.next()?;
+        let index = self.index;
+        self.index += 1;
+
+        if self.sendable(c) {
+            return Some(Ok(c));
+        }
+
+        // Letters which upper-case to more than one character, such as ß,
+        // are left to the transliterations
+        let mut upper = c.to_uppercase();
+        let folded = match (upper.next(), upper.next()) {
+            (Some(u), None) if self.fold_case => u,
+            _ => c,
+        };
+        if self.sendable(folded) {
+            return Some(Ok(folded));
+        }
+
+        let replacements = [
+            if self.transliterate { transliteration(folded) } else { None },
+            if self.spell_out { spelling(folded) } else { None },
+        ];
+        for replacement in replacements.into_iter().flatten() {
+            if self.all_sendable(replacement) {
+                self.pending = replacement.chars();
+                return self.pending.next().map(Ok);
+            }
+        }
+
+        Some(Err(Unrepresentable { index, found: c }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(text: &str, set: CharacterSet) -> Normalizer<Chars<'_>> {
+        Normalizer::new(text.chars(), set)
+    }
+
+    #[test]
+    fn folds_transliterates_and_spells_out() {
+        let text = "Grüße, “Jürgen” @ café; 50%";
+
+        assert!(normalized(text, CharacterSet::Uk).map(Result::unwrap).eq("GRUESSE, 'JUERGEN' AT CAFE, 50PERCENT".chars()));
+    }
+
+    #[test]
+    fn keeps_what_the_character_set_can_send() {
+        assert!(normalized("\"5$\" & 6£", CharacterSet::UsTty).map(Result::unwrap).eq("\"5$\" & 6POUNDS".chars()));
+        assert!(normalized("Да", CharacterSet::Mtk2).map(Result::unwrap).eq("ДА".chars()));
+    }
+
+    #[test]
+    fn reports_what_cannot_be_sent() {
+        let mut chars = normalized("ok ~ü", CharacterSet::Uk).with_transliteration(false);
+
+        assert_eq!(chars.nth(3), Some(Err(Unrepresentable { index: 3, found: '~' })));
+        assert_eq!(chars.next(), Some(Err(Unrepresentable { index: 4, found: 'ü' })));
+    }
+
+    #[test]
+    fn stages_can_be_turned_off() {
+        let strict = normalized("a@", CharacterSet::Uk).with_fold_case(false).with_spelling_out(false);
+
+        assert_eq!(strict.lossy().next(), None);
+        assert!(normalized("a@b", CharacterSet::Uk).with_spelling_out(false).lossy().eq("AB".chars()));
+    }
+}
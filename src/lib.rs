@@ -0,0 +1,22 @@
+//! The Lorenz SZ40/42 cipher machine and the ITA2 teleprinter code it worked on.
+//!
+//! The crate is `no_std` and allocation free, so the same core runs on the
+//! Arduino firmware and on a host.
+
+#![no_std]
+
+// The tests may allocate to build strings; the library itself never does
+#[cfg(test)]
+extern crate std;
+
+pub mod colossus;
+pub mod delta;
+pub mod framing;
+pub mod ita2;
+pub mod lorenz;
+pub mod modem;
+pub mod robinson;
+pub mod tape;
+pub mod tutte;
+
+pub use lorenz::{LorenzMachine, LorenzWheel, MachineModel, MessageSettings, Wheel, WheelPatterns};
@@ -0,0 +1,249 @@
+//! The rules German operators had to follow when setting wheel patterns, and
+//! a key generator that only produces patterns which follow them.
+//!
+//! The chi and psi wheels must be within one cam of half crosses and may not
+//! have more than four like cams in a row. The motor wheels were looser; the
+//! limits used here, between a quarter and three quarters crosses with no more
+//! than six like cams in a row, are this crate's approximation rather than a
+//! documented rule. Runs are counted around the wheel, so a run may wrap from
+//! the last cam back to the first.
+
+use core::fmt;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use super::{MessageSettings, WheelPatterns};
+
+/// The constraints a single wheel's pattern must satisfy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CamRules {
+    pub min_crosses: usize,
+    pub max_crosses: usize,
+    /// The longest run of like cams allowed
+    pub max_run: usize,
+}
+
+/// The first rule a pattern breaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CamRuleViolation {
+    TooFewCrosses { crosses: usize, min: usize },
+    TooManyCrosses { crosses: usize, max: usize },
+    /// A run of `length` like cams begins at cam `start`.
+    RunTooLong { start: usize, length: usize, max: usize },
+}
+
+/// A wheel, by index in machine order, whose pattern breaks a rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PatternError {
+    pub wheel: usize,
+    pub violation: CamRuleViolation,
+}
+
+impl fmt::Display for CamRuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CamRuleViolation::TooFewCrosses { crosses, min } =>
+                write!(f, "{crosses} crosses, at least {min} are needed"),
+            CamRuleViolation::TooManyCrosses { crosses, max } =>
+                write!(f, "{crosses} crosses, at most {max} are allowed"),
+            CamRuleViolation::RunTooLong { start, length, max } =>
+                write!(f, "a run of {length} like cams from cam {start}, at most {max} are allowed"),
+        }
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", MessageSettings::WHEEL_NAMES[self.wheel], self.violation)
+    }
+}
+
+impl CamRules {
+    /// The rules for a wheel by its index in machine order, or `None` past
+    /// the twelfth wheel.
+    pub const fn for_wheel(wheel: usize) -> Option<CamRules> {
+        if wheel >= MessageSettings::WHEEL_LENGTHS.len() {
+            return None;
+        }
+        let length = MessageSettings::WHEEL_LENGTHS[wheel];
+
+        Some(match wheel {
+            // μ37 and μ61
+            5 | 6 => CamRules {
+                min_crosses: length / 4,
+                max_crosses: length * 3 / 4,
+                max_run: 6,
+            },
+            // |2 × crosses - length| <= 2, so an odd wheel is half a cam
+            // either side of half
+            _ => CamRules {
+                min_crosses: (length - 1) / 2,
+                max_crosses: (length + 2) / 2,
+                max_run: 4,
+            },
+        })
+    }
+
+    pub fn check(&self, pattern: &[bool]) -> Result<(), CamRuleViolation> {
+        let crosses = pattern.iter().filter(|&&cam| cam).count();
+
+        if crosses < self.min_crosses {
+            return Err(CamRuleViolation::TooFewCrosses { crosses, min: self.min_crosses });
+        }
+        if crosses > self.max_crosses {
+            return Err(CamRuleViolation::TooManyCrosses { crosses, max: self.max_crosses });
+        }
+
+        let n = pattern.len();
+        // Start counting at the beginning of a run, so runs wrapping round are whole
+        let Some(first) = (0..n).find(|&i| pattern[i] != pattern[(i + n - 1) % n]) else {
+            return if n > self.max_run {
+                Err(CamRuleViolation::RunTooLong { start: 0, length: n, max: self.max_run })
+            } else {
+                Ok(())
+            };
+        };
+
+        let mut start = first;
+        while start < first + n {
+            let length = run_length(pattern, start % n);
+            if length > self.max_run {
+                return Err(CamRuleViolation::RunTooLong { start: start % n, length, max: self.max_run });
+            }
+            start += length;
+        }
+
+        Ok(())
+    }
+
+    /// Fills `pattern` with a random pattern following these rules.
+    pub fn generate(&self, pattern: &mut [bool], rng: &mut impl Rng) {
+        loop {
+            let crosses = rng.gen_range(self.min_crosses..=self.max_crosses);
+
+            for (i, cam) in pattern.iter_mut().enumerate() {
+                *cam = i < crosses;
+            }
+            pattern.shuffle(rng);
+
+            if self.check(pattern).is_ok() {
+                return;
+            }
+        }
+    }
+}
+
+/// The number of like cams in a row from `start`, wrapping round the wheel.
+fn run_length(pattern: &[bool], start: usize) -> usize {
+    let n = pattern.len();
+    (0..n).take_while(|&i| pattern[(start + i) % n] == pattern[start]).count()
+}
+
+impl WheelPatterns {
+    /// Random patterns for all twelve wheels, each following its `CamRules`.
+    pub fn new_random(rng: &mut impl Rng) -> Self {
+        let mut patterns = WheelPatterns::new_zeroed();
+
+        for (wheel, rules) in (0..).map_while(CamRules::for_wheel).enumerate() {
+            rules.generate(patterns.wheel_mut(wheel), rng);
+        }
+
+        patterns
+    }
+
+    /// Checks every wheel against its `CamRules`, reporting the first wheel
+    /// which breaks one.
+    pub fn check(&self) -> Result<(), PatternError> {
+        for (wheel, rules) in (0..).map_while(CamRules::for_wheel).enumerate() {
+            rules.check(self.wheel(wheel)).map_err(|violation| PatternError { wheel, violation })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn pattern<const N: usize>(cams: &str) -> [bool; N] {
+        let mut out = [false; N];
+        for (cam, c) in out.iter_mut().zip(cams.chars()) {
+            *cam = c == 'x';
+        }
+        out
+    }
+
+    #[test]
+    fn generated_patterns_follow_the_rules() {
+        let mut rng = StdRng::seed_from_u64(57);
+
+        for _ in 0..20 {
+            assert_eq!(WheelPatterns::new_random(&mut rng).check(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn has_rules_for_twelve_wheels() {
+        assert!(CamRules::for_wheel(11).is_some());
+        assert_eq!(CamRules::for_wheel(12), None);
+    }
+
+    #[test]
+    fn reports_unbalanced_wheels() {
+        let rules = CamRules::for_wheel(11).unwrap();
+
+        assert_eq!(
+            rules.check(&pattern::<23>("x.x.x.x.x.x.x.x.x.....")),
+            Err(CamRuleViolation::TooFewCrosses { crosses: 9, min: 11 })
+        );
+        assert_eq!(
+            rules.check(&pattern::<23>("xx.xx.xx.xx.xx.xx.xx.xx")),
+            Err(CamRuleViolation::TooManyCrosses { crosses: 16, max: 12 })
+        );
+    }
+
+    #[test]
+    fn keeps_odd_wheels_within_one_of_half() {
+        let rules = CamRules::for_wheel(11).unwrap();
+
+        assert_eq!(
+            rules.check(&pattern::<23>("x.x.x.x.x..x.x.x..x.x..")),
+            Err(CamRuleViolation::TooFewCrosses { crosses: 10, min: 11 })
+        );
+        assert_eq!(rules.check(&pattern::<23>("x.x.x.x.x.xx.x.x..x.x..")), Ok(()));
+        assert_eq!(rules.check(&pattern::<23>("x.x.x.x.x.xx.x.xx.x.x..")), Ok(()));
+        assert_eq!(
+            rules.check(&pattern::<23>("x.x.x.x.x.xx.x.xx.x.xx.")),
+            Err(CamRuleViolation::TooManyCrosses { crosses: 13, max: 12 })
+        );
+
+        let even = CamRules::for_wheel(10).unwrap();
+        assert_eq!((even.min_crosses, even.max_crosses), (12, 14));
+    }
+
+    #[test]
+    fn reports_runs_wrapping_round() {
+        let rules = CamRules::for_wheel(11).unwrap();
+
+        assert_eq!(rules.check(&pattern::<23>("xxx.x.x..x.x.x..x.x..xx")), Err(
+            CamRuleViolation::RunTooLong { start: 21, length: 5, max: 4 }
+        ));
+        assert_eq!(rules.check(&pattern::<23>("xx.x.x..x.x.x..x.x.x..x")), Ok(()));
+    }
+
+    #[test]
+    fn reports_the_offending_wheel() {
+        let mut patterns = WheelPatterns::new_random(&mut StdRng::seed_from_u64(57));
+        patterns.mu_61 = [false; 61];
+
+        assert_eq!(patterns.check(), Err(PatternError {
+            wheel: 6,
+            violation: CamRuleViolation::TooFewCrosses { crosses: 0, min: 15 },
+        }));
+    }
+}
@@ -0,0 +1,313 @@
+//! Message indicators: how the start positions reached the receiving operator.
+//!
+//! Until late 1942 the sender picked twelve letters, one per wheel, and sent
+//! them in clear, spelled out, at the head of the message. Each wheel had its
+//! own table mapping letters to start positions. After that the settings were
+//! taken from a numbered entry in a QEP book, and only `QEP` and the entry
+//! number were sent.
+
+use core::fmt;
+use core::str::FromStr;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use super::MessageSettings;
+
+/// The German spelling alphabet the letters of an indicator were sent in.
+const SPELLING: [&str; 26] = [
+    "ANTON", "BERTA", "CAESAR", "DORA", "EMIL", "FRIEDRICH", "GUSTAV", "HEINRICH", "IDA", "JULIUS",
+    "KAUFMANN", "LUDWIG", "MARTHA", "NORDPOL", "OTTO", "PAULA", "QUELLE", "RICHARD", "SIEGFRIED",
+    "THEODOR", "ULRICH", "VIKTOR", "WILHELM", "XANTHIPPE", "YPSILON", "ZACHARIAS",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndicatorError {
+    /// An indicator must be exactly twelve letters.
+    WrongLength { found: usize },
+    /// A character of the indicator is not a letter.
+    NotALetter { index: usize, found: char },
+    /// The wheel's table has no position for this letter.
+    LetterNotInTable { wheel: usize, letter: char },
+    /// The wheel's table has no letter for this position.
+    PositionNotInTable { wheel: usize, position: u8 },
+    /// The QEP book has no entry with this number.
+    UnknownQep { number: u16 },
+}
+
+impl fmt::Display for IndicatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |wheel: usize| MessageSettings::WHEEL_NAMES[wheel];
+
+        match *self {
+            IndicatorError::WrongLength { found } => write!(f, "an indicator has 12 letters, not {found}"),
+            IndicatorError::NotALetter { index, found } => write!(f, "character {index} is {found:?}, not a letter"),
+            IndicatorError::LetterNotInTable { wheel, letter } => write!(f, "{} has no position for {letter}", name(wheel)),
+            IndicatorError::PositionNotInTable { wheel, position } => write!(f, "{} has no letter for position {position}", name(wheel)),
+            IndicatorError::UnknownQep { number } => write!(f, "there is no QEP {number:02}"),
+        }
+    }
+}
+
+/// Twelve letters, one per wheel in machine order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Indicator([u8; 12]);
+
+impl Indicator {
+    /// The letters as ASCII upper case.
+    pub fn letters(&self) -> &[u8; 12] {
+        &self.0
+    }
+
+    /// The indicator as sent, each letter spelled out in the German spelling
+    /// alphabet.
+    pub fn spelled(&self) -> Spelled<'_> {
+        Spelled(self)
+    }
+}
+
+impl FromStr for Indicator {
+    type Err = IndicatorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let found = s.chars().count();
+        if found != 12 {
+            return Err(IndicatorError::WrongLength { found });
+        }
+
+        let mut letters = [0; 12];
+        for (index, (c, letter)) in s.chars().zip(letters.iter_mut()).enumerate() {
+            if !c.is_ascii_alphabetic() {
+                return Err(IndicatorError::NotALetter { index, found: c });
+            }
+            *letter = c.to_ascii_uppercase() as u8;
+        }
+
+        Ok(Indicator(letters))
+    }
+}
+
+impl fmt::Display for Indicator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &letter in &self.0 {
+            write!(f, "{}", letter as char)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Spelled<'a>(&'a Indicator);
+
+impl fmt::Display for Spelled<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &letter) in self.0.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(SPELLING[(letter - b'A') as usize])?;
+        }
+        Ok(())
+    }
+}
+
+/// For each wheel, the start position each letter stands for. A wheel with
+/// fewer than 26 positions leaves some letters unused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IndicatorTable {
+    positions: [[Option<u8>; 26]; 12],
+}
+
+impl IndicatorTable {
+    /// Builds a table from the position of each letter `A`-`Z` on each wheel.
+    pub const fn new(positions: [[Option<u8>; 26]; 12]) -> Self {
+        IndicatorTable { positions }
+    }
+
+    /// A table giving each letter a different random position on every wheel.
+    pub fn new_random(rng: &mut impl Rng) -> Self {
+        let mut positions = [[None; 26]; 12];
+
+        for (wheel, letters) in positions.iter_mut().enumerate() {
+            let mut all = [0u8; 61];
+            let all = &mut all[..MessageSettings::WHEEL_LENGTHS[wheel]];
+            for (position, p) in all.iter_mut().enumerate() {
+                *p = position as u8;
+            }
+            all.shuffle(rng);
+
+            let mut order = [0usize; 26];
+            for (i, o) in order.iter_mut().enumerate() {
+                *o = i;
+            }
+            order.shuffle(rng);
+
+            for (&letter, &position) in order.iter().zip(all.iter()) {
+                letters[letter] = Some(position);
+            }
+        }
+
+        IndicatorTable { positions }
+    }
+
+    /// The start positions an indicator stands for.
+    pub fn settings(&self, indicator: &Indicator) -> Result<MessageSettings, IndicatorError> {
+        let mut positions = [0; 12];
+
+        for (wheel, (&letter, position)) in indicator.0.iter().zip(positions.iter_mut()).enumerate() {
+            *position = self.positions[wheel][(letter - b'A') as usize]
+                .ok_or(IndicatorError::LetterNotInTable { wheel, letter: letter as char })?;
+        }
+
+        Ok(MessageSettings::from_array(positions))
+    }
+
+    /// The indicator standing for a set of start positions.
+    pub fn indicator(&self, settings: &MessageSettings) -> Result<Indicator, IndicatorError> {
+        let mut letters = [0; 12];
+
+        for (wheel, (&position, letter)) in settings.to_array().iter().zip(letters.iter_mut()).enumerate() {
+            let index = self.positions[wheel].iter()
+                .position(|&p| p == Some(position))
+                .ok_or(IndicatorError::PositionNotInTable { wheel, position })?;
+            *letter = b'A' + index as u8;
+        }
+
+        Ok(Indicator(letters))
+    }
+}
+
+/// A book of message settings, looked up by the number sent after `QEP`.
+pub trait QepBook {
+    fn settings(&self, number: u16) -> Option<MessageSettings>;
+}
+
+/// Entries are numbered from 1, as in the printed books.
+impl QepBook for [MessageSettings] {
+    fn settings(&self, number: u16) -> Option<MessageSettings> {
+        self.get((number as usize).checked_sub(1)?).copied()
+    }
+}
+
+impl<const N: usize> QepBook for [MessageSettings; N] {
+    fn settings(&self, number: u16) -> Option<MessageSettings> {
+        self.as_slice().settings(number)
+    }
+}
+
+/// What is sent in clear ahead of the cipher text to give the settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Preamble {
+    Indicator(Indicator),
+    Qep(u16),
+}
+
+impl Preamble {
+    /// The start positions the preamble stands for. Only the table or the book
+    /// is consulted, depending on the kind of preamble.
+    pub fn settings(&self, table: &IndicatorTable, book: &(impl QepBook + ?Sized)) -> Result<MessageSettings, IndicatorError> {
+        match self {
+            Preamble::Indicator(indicator) => table.settings(indicator),
+            Preamble::Qep(number) => book.settings(*number).ok_or(IndicatorError::UnknownQep { number: *number }),
+        }
+    }
+}
+
+impl fmt::Display for Preamble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Preamble::Indicator(indicator) => write!(f, "{}", indicator.spelled()),
+            Preamble::Qep(number) => write!(f, "QEP {number:02}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    /// Letter `A` is position 0, `B` position 1 and so on, as far as each wheel goes.
+    fn alphabetical_table() -> IndicatorTable {
+        let mut positions = [[None; 26]; 12];
+        for (wheel, letters) in positions.iter_mut().enumerate() {
+            for (letter, position) in letters.iter_mut().enumerate() {
+                if letter < MessageSettings::WHEEL_LENGTHS[wheel] {
+                    *position = Some(letter as u8);
+                }
+            }
+        }
+        IndicatorTable::new(positions)
+    }
+
+    #[test]
+    fn indicator_round_trips_through_settings() {
+        let table = alphabetical_table();
+        let indicator: Indicator = "HQIBPEXEZMUG".parse().unwrap();
+
+        let settings = table.settings(&indicator).unwrap();
+
+        assert_eq!(settings.to_array(), [7, 16, 8, 1, 15, 4, 23, 4, 25, 12, 20, 6]);
+        assert_eq!(table.indicator(&settings), Ok(indicator));
+    }
+
+    #[test]
+    fn random_tables_give_distinct_positions() {
+        let table = IndicatorTable::new_random(&mut StdRng::seed_from_u64(57));
+
+        for (wheel, letters) in table.positions.iter().enumerate() {
+            let length = MessageSettings::WHEEL_LENGTHS[wheel];
+            let mut seen = [false; 61];
+
+            for &position in letters.iter().flatten() {
+                assert!((position as usize) < length);
+                assert!(!seen[position as usize]);
+                seen[position as usize] = true;
+            }
+            assert_eq!(seen.iter().filter(|&&s| s).count(), length.min(26));
+        }
+    }
+
+    #[test]
+    fn reports_bad_indicators() {
+        assert_eq!("HQIBPEXEZMU".parse::<Indicator>(), Err(IndicatorError::WrongLength { found: 11 }));
+        assert_eq!("HQIBPEXEZMU7".parse::<Indicator>(), Err(IndicatorError::NotALetter { index: 11, found: '7' }));
+
+        let table = IndicatorTable::new([[Some(0); 26]; 12]);
+        let settings = MessageSettings::from_array([0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(table.indicator(&settings), Err(IndicatorError::PositionNotInTable { wheel: 3, position: 1 }));
+
+        let table = IndicatorTable::new([[None; 26]; 12]);
+        let indicator = "hqibpexezmug".parse().unwrap();
+        assert_eq!(table.settings(&indicator), Err(IndicatorError::LetterNotInTable { wheel: 0, letter: 'H' }));
+    }
+
+    #[test]
+    fn preambles_look_up_their_settings() {
+        let table = IndicatorTable::new([[Some(4); 26]; 12]);
+        let book = [
+            MessageSettings::from_array([1; 12]),
+            MessageSettings::from_array([2; 12]),
+        ];
+
+        assert_eq!(Preamble::Qep(2).settings(&table, &book), Ok(book[1]));
+        assert_eq!(Preamble::Qep(0).settings(&table, &book), Err(IndicatorError::UnknownQep { number: 0 }));
+        assert_eq!(Preamble::Qep(3).settings(&table, &book), Err(IndicatorError::UnknownQep { number: 3 }));
+
+        let indicator = Preamble::Indicator("AAAAAAAAAAAA".parse().unwrap());
+        assert_eq!(indicator.settings(&table, &book), Ok(MessageSettings::from_array([4; 12])));
+    }
+
+    #[test]
+    fn preambles_are_sent_spelled_out() {
+        let indicator = Preamble::Indicator("HQIBPEXEZMUG".parse().unwrap());
+
+        assert_eq!(
+            format!("{indicator}|{}", Preamble::Qep(7)),
+            "HEINRICH QUELLE IDA BERTA PAULA EMIL XANTHIPPE EMIL ZACHARIAS MARTHA ULRICH GUSTAV|QEP 07"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! A human-readable key file holding the wheel patterns and start positions.
+//!
+//! Each wheel takes one line: its name, its length, its cams in the
+//! traditional notation (`x` for a raised cam, `.` for a lowered one) and its
+//! start position counting from 0. Blank lines and lines starting with `#` are
+//! ignored. All twelve wheels must appear exactly once, in any order.
+//!
+//! ```text
+//! # psi wheels
+//! psi1 43 x..xx.x.x..x.xx..x.x.x.xx..x..x.x.xx.x..x.x 12
+//! ...
+//! chi5 23 ...xx..xx...xxxxxxx..x. 0
+//! ```
+
+use core::fmt;
+use core::str::FromStr;
+
+use super::{MessageSettings, WheelPatterns};
+
+/// A complete key: the wheel patterns and a message's start positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub patterns: WheelPatterns,
+    pub settings: MessageSettings,
+}
+
+/// Why a key file could not be read. Lines are numbered from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyError {
+    /// The line does not name one of the twelve wheels.
+    UnknownWheel { line: usize },
+    /// The line stops before the length, cams or start position.
+    MissingField { line: usize },
+    /// The line has something after the start position.
+    UnexpectedField { line: usize },
+    /// The length is not a number.
+    MalformedLength { line: usize },
+    /// The stated length is not the length of the named wheel.
+    WrongLength { line: usize, wheel: usize, found: usize },
+    /// The cam string is not as long as the wheel.
+    WrongCamCount { line: usize, wheel: usize, found: usize },
+    /// A cam is neither `x` nor `.`.
+    InvalidCam { line: usize, cam: usize, found: char },
+    /// The start position is not a number.
+    MalformedPosition { line: usize },
+    /// The start position is past the end of the wheel.
+    PositionOutOfRange { line: usize, wheel: usize, position: usize },
+    /// The wheel has already been given.
+    DuplicateWheel { line: usize, wheel: usize },
+    /// The wheel never appears in the file.
+    MissingWheel { wheel: usize },
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |wheel: usize| MessageSettings::WHEEL_NAMES[wheel];
+        let length = |wheel: usize| MessageSettings::WHEEL_LENGTHS[wheel];
+
+        match *self {
+            KeyError::UnknownWheel { line } => write!(f, "line {line}: unknown wheel"),
+            KeyError::MissingField { line } => write!(f, "line {line}: expected a name, length, cams and start position"),
+            KeyError::UnexpectedField { line } => write!(f, "line {line}: unexpected text after the start position"),
+            KeyError::MalformedLength { line } => write!(f, "line {line}: the length is not a number"),
+            KeyError::WrongLength { line, wheel, found } =>
+                write!(f, "line {line}: {} has {} cams, not {found}", name(wheel), length(wheel)),
+            KeyError::WrongCamCount { line, wheel, found } =>
+                write!(f, "line {line}: {} needs {} cams, found {found}", name(wheel), length(wheel)),
+            KeyError::InvalidCam { line, cam, found } =>
+                write!(f, "line {line}: cam {cam} is {found:?}, expected 'x' or '.'"),
+            KeyError::MalformedPosition { line } => write!(f, "line {line}: the start position is not a number"),
+            KeyError::PositionOutOfRange { line, wheel, position } =>
+                write!(f, "line {line}: {} has no position {position}", name(wheel)),
+            KeyError::DuplicateWheel { line, wheel } => write!(f, "line {line}: {} is given twice", name(wheel)),
+            KeyError::MissingWheel { wheel } => write!(f, "{} is missing", name(wheel)),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = KeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patterns = WheelPatterns::new_zeroed();
+        let mut positions = [0; 12];
+        let mut seen = [false; 12];
+
+        for (i, text) in s.lines().enumerate() {
+            let line = i + 1;
+            let text = text.trim();
+
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = text.split_whitespace();
+            let name = fields.next().ok_or(KeyError::MissingField { line })?;
+            let wheel = MessageSettings::WHEEL_NAMES.iter()
+                .position(|&n| n == name)
+                .ok_or(KeyError::UnknownWheel { line })?;
+            let expected = MessageSettings::WHEEL_LENGTHS[wheel];
+
+            if seen[wheel] {
+                return Err(KeyError::DuplicateWheel { line, wheel });
+            }
+            seen[wheel] = true;
+
+            let length: usize = fields.next().ok_or(KeyError::MissingField { line })?
+                .parse()
+                .map_err(|_| KeyError::MalformedLength { line })?;
+            if length != expected {
+                return Err(KeyError::WrongLength { line, wheel, found: length });
+            }
+
+            let cams = fields.next().ok_or(KeyError::MissingField { line })?;
+            let found = cams.chars().count();
+            if found != expected {
+                return Err(KeyError::WrongCamCount { line, wheel, found });
+            }
+            for (cam, (c, out)) in cams.chars().zip(patterns.wheel_mut(wheel).iter_mut()).enumerate() {
+                *out = match c {
+                    'x' => true,
+                    '.' => false,
+                    found => return Err(KeyError::InvalidCam { line, cam, found }),
+                };
+            }
+
+            let position: usize = fields.next().ok_or(KeyError::MissingField { line })?
+                .parse()
+                .map_err(|_| KeyError::MalformedPosition { line })?;
+            if position >= expected {
+                return Err(KeyError::PositionOutOfRange { line, wheel, position });
+            }
+            positions[wheel] = position as u8;
+
+            if fields.next().is_some() {
+                return Err(KeyError::UnexpectedField { line });
+            }
+        }
+
+        if let Some(wheel) = seen.iter().position(|&s| !s) {
+            return Err(KeyError::MissingWheel { wheel });
+        }
+
+        Ok(Key {
+            patterns,
+            settings: MessageSettings::from_array(positions),
+        })
+    }
+}
+
+/// Writes the key in the same format `from_str` reads, one wheel per line in
+/// machine order.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positions = self.settings.to_array();
+
+        for (wheel, name) in MessageSettings::WHEEL_NAMES.iter().enumerate() {
+            let pattern = self.patterns.wheel(wheel);
+
+            write!(f, "{name} {} ", pattern.len())?;
+            for &cam in pattern {
+                f.write_str(if cam { "x" } else { "." })?;
+            }
+            writeln!(f, " {}", positions[wheel])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::{String, ToString};
+
+    use super::*;
+
+    const KEY: &str = "\
+# A test key
+psi1 43 x.x.xxxx..x.xxxx..x.xxx.xxxxxxxx..xxxx..x.. 12
+psi2 47 xx.x..x.x.....x..xx.x..x...x.xxxxx.....x..x.xx. 0
+psi3 51 .x..x...xxx..xxx.xx....xxx..xxx.xx..xx..x.x........ 50
+psi4 53 xx.x..xx...x.x.......xx............x...xxx...x.x...xx 1
+psi5 59 x.xxxx.xxxxx.x.xx....xxx.x....x.x.x....x....xx..xxx.x.x.x.. 58
+
+mu37 37 ..x.....x.....x....x.xx.x.xx.x..x.xx. 3
+mu61 61 .x....xxx....x...xxx..xxx......x..x.x....xx...x.xxx.xxx..x..x 60
+chi1 41 xxxxx..xxx.xxxxx.x.xx.xx.x.....xx.x.x.xx. 40
+chi2 31 .xx...xxx.xxxx.x....xx.xx.xxx.x 30
+chi3 29 x..x.....x....xxx.xxxxxxxx... 7
+chi4 26 x.......xxxxx...x.x.x.xx.x 13
+chi5 23 ...xx..xx...xxxxxxx..x. 0
+";
+
+    fn replace_line(key: &str, line: usize, text: &str) -> String {
+        let mut replaced = String::new();
+        for (i, l) in key.lines().enumerate() {
+            replaced.push_str(if i + 1 == line { text } else { l });
+            replaced.push('\n');
+        }
+        replaced
+    }
+
+    #[test]
+    fn parses_patterns_and_positions() {
+        let key: Key = KEY.parse().unwrap();
+
+        assert_eq!(key.settings.to_array(), [12, 0, 50, 1, 58, 3, 60, 40, 30, 7, 13, 0]);
+        assert!(key.patterns.psi_1[0]);
+        assert!(!key.patterns.psi_1[1]);
+        assert!(key.patterns.chi_5[21]);
+        assert!(!key.patterns.chi_5[22]);
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let key: Key = KEY.parse().unwrap();
+
+        assert_eq!(key.to_string().parse::<Key>(), Ok(key));
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let cases = [
+            ("chi6 23 ...xx..xx...xxxxxxx..x. 0", KeyError::UnknownWheel { line: 14 }),
+            ("chi5 23 ...xx..xx...xxxxxxx..x.", KeyError::MissingField { line: 14 }),
+            ("chi5 23 ...xx..xx...xxxxxxx..x. 0 0", KeyError::UnexpectedField { line: 14 }),
+            ("chi5 2x ...xx..xx...xxxxxxx..x. 0", KeyError::MalformedLength { line: 14 }),
+            ("chi5 26 ...xx..xx...xxxxxxx..x. 0", KeyError::WrongLength { line: 14, wheel: 11, found: 26 }),
+            ("chi5 23 x.x..xx.x..x.xx.x..x.x 0", KeyError::WrongCamCount { line: 14, wheel: 11, found: 22 }),
+            ("chi5 23 x.x..xx.x..x.xx.x..x.xo 0", KeyError::InvalidCam { line: 14, cam: 22, found: 'o' }),
+            ("chi5 23 ...xx..xx...xxxxxxx..x. -1", KeyError::MalformedPosition { line: 14 }),
+            ("chi5 23 ...xx..xx...xxxxxxx..x. 23", KeyError::PositionOutOfRange { line: 14, wheel: 11, position: 23 }),
+            ("chi4 26 x.......xxxxx...x.x.x.xx.x 13", KeyError::DuplicateWheel { line: 14, wheel: 10 }),
+            ("# chi5 is gone", KeyError::MissingWheel { wheel: 11 }),
+        ];
+
+        for (line, error) in cases {
+            assert_eq!(replace_line(KEY, 14, line).parse::<Key>(), Err(error), "{line}");
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! The machine's key, independent of any plaintext, for analysis and teaching.
+
+use core::iter::Map;
+
+use super::{LorenzChiWheels, LorenzMachine, LorenzPsiWheels, MachineModel};
+
+/// Everything the machine contributes to one character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyCharacter {
+    /// The five chi wheels, impulse 1 in the least significant bit
+    pub chi: u8,
+    /// The five psi wheels as they stand (ψ'), impulse 1 in the least significant bit
+    pub psi: u8,
+    /// μ37, the basic motor
+    pub basic_motor: bool,
+    /// Whether the psi wheels move on after this character
+    pub total_motor: bool,
+    /// chi ⊕ psi, the key added to the plaintext
+    pub key: u8,
+}
+
+/// An endless stream of `KeyCharacter`s, stepping the machine after each one.
+/// The machine is stepped as if every plaintext character were null, which
+/// only matters with the P5 limitation.
+pub struct KeyStream<'a> {
+    machine: &'a mut LorenzMachine,
+}
+
+impl<'a> KeyStream<'a> {
+    /// The machine as it stands before the next character.
+    pub fn machine(&self) -> &LorenzMachine {
+        self.machine
+    }
+}
+
+impl<'a> Iterator for KeyStream<'a> {
+    type Item = KeyCharacter;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let machine = &mut *self.machine;
+        let chi = machine.chi.read_all();
+        let psi = machine.psi.read_all();
+        let total_motor = match machine.model {
+            MachineModel::SZ40 | MachineModel::SZ42A | MachineModel::SZ42B =>
+                machine.mu.total_motor(machine.model, machine.p5_limitation),
+            MachineModel::Demo => true,
+        };
+
+        let character = KeyCharacter {
+            chi,
+            psi,
+            basic_motor: machine.mu.f.read_head(),
+            total_motor,
+            key: chi ^ psi,
+        };
+        machine.step_machine();
+
+        Some(character)
+    }
+}
+
+pub type Stream<'a, T> = Map<KeyStream<'a>, fn(KeyCharacter) -> T>;
+
+/// The chi wheels on their own, stepping every character from where they
+/// stand, without moving the machine.
+#[derive(Clone)]
+pub struct ChiWheels(LorenzChiWheels);
+
+impl Iterator for ChiWheels {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chi = self.0.read_all();
+        self.0.step_all();
+        Some(chi)
+    }
+}
+
+/// The psi wheels on their own, stepping every character from where they
+/// stand: the unextended ψ, without the motor's repeats.
+#[derive(Clone)]
+pub struct PsiWheels(LorenzPsiWheels);
+
+impl Iterator for PsiWheels {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let psi = self.0.read_all();
+        self.0.step_all();
+        Some(psi)
+    }
+}
+
+impl LorenzMachine {
+    pub fn key_stream(&mut self) -> KeyStream<'_> {
+        KeyStream { machine: self }
+    }
+
+    /// The combined key, chi ⊕ psi.
+    pub fn key_codes(&mut self) -> Stream<'_, u8> {
+        self.key_stream().map(|k| k.key)
+    }
+
+    pub fn chi_stream(&mut self) -> Stream<'_, u8> {
+        self.key_stream().map(|k| k.chi)
+    }
+
+    /// The extended psi stream ψ', with repeats wherever the psi wheels stood still.
+    pub fn psi_stream(&mut self) -> Stream<'_, u8> {
+        self.key_stream().map(|k| k.psi)
+    }
+
+    /// The total motor, `true` wherever the psi wheels move on.
+    pub fn motor_stream(&mut self) -> Stream<'_, bool> {
+        self.key_stream().map(|k| k.total_motor)
+    }
+
+    pub fn chi_wheels(&self) -> ChiWheels {
+        ChiWheels(self.chi.clone())
+    }
+
+    pub fn psi_wheels(&self) -> PsiWheels {
+        PsiWheels(self.psi.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn machine(model: MachineModel) -> LorenzMachine {
+        LorenzMachine::new_random(&mut StdRng::seed_from_u64(57)).with_model(model)
+    }
+
+    #[test]
+    fn key_matches_encipherment_of_nulls() {
+        for model in [MachineModel::SZ40, MachineModel::SZ42A, MachineModel::SZ42B, MachineModel::Demo] {
+            let mut a = machine(model);
+            let mut b = machine(model);
+
+            assert!(a.key_codes().take(500).eq(b.process([0; 500])));
+        }
+    }
+
+    #[test]
+    fn psi_only_moves_with_the_motor() {
+        let mut machine = machine(MachineModel::SZ42B);
+        let mut previous: Option<KeyCharacter> = None;
+        let mut positions = machine.message_settings().psi;
+
+        for _ in 0..500 {
+            let character = machine.key_stream().next().unwrap();
+            let moved = machine.message_settings().psi != positions;
+            positions = machine.message_settings().psi;
+
+            assert_eq!(character.key, character.chi ^ character.psi);
+            assert_eq!(moved, character.total_motor);
+            if let Some(previous) = previous.filter(|p| !p.total_motor) {
+                assert_eq!(previous.psi, character.psi);
+            }
+            previous = Some(character);
+        }
+    }
+
+    #[test]
+    fn separate_streams_agree_with_records() {
+        let mut records = machine(MachineModel::SZ40);
+        let mut chi = machine(MachineModel::SZ40);
+        let mut psi = machine(MachineModel::SZ40);
+        let mut motor = machine(MachineModel::SZ40);
+
+        let records = records.key_stream().take(200);
+        let streams = chi.chi_stream().zip(psi.psi_stream()).zip(motor.motor_stream());
+
+        for (record, ((chi, psi), motor)) in records.zip(streams) {
+            assert_eq!((record.chi, record.psi, record.total_motor), (chi, psi, motor));
+        }
+    }
+
+    #[test]
+    fn wheel_streams_leave_out_the_motor() {
+        let mut machine = machine(MachineModel::SZ40);
+        let chi = machine.chi_wheels();
+        let psi = machine.psi_wheels();
+        let mut motor = machine.clone();
+
+        // With every μ37 cam raised the psi wheels move every character
+        let mut patterns = machine.patterns();
+        patterns.wheel_mut(5).fill(true);
+        let mut always_moving = LorenzMachine::new(&patterns);
+        always_moving.set_message_settings(&machine.message_settings()).unwrap();
+
+        assert!(chi.take(200).eq(always_moving.clone().chi_stream().take(200)));
+        assert!(psi.clone().take(200).eq(always_moving.psi_stream().take(200)));
+
+        // ψ' is ψ with each character held for as long as the motor stands still
+        let mut unextended = psi;
+        let mut current = unextended.next();
+        let extended = motor.motor_stream().take(200).map(|moves| {
+            let psi = current;
+            if moves {
+                current = unextended.next();
+            }
+            psi.unwrap()
+        });
+        assert!(extended.eq(machine.psi_stream().take(200)));
+    }
+}
@@ -0,0 +1,209 @@
+//! Snapshots of everything that decides a machine's future output.
+
+use core::fmt;
+
+use super::{LorenzMachine, MachineModel, MessageSettings, WheelPatterns};
+
+/// A compact copy of a machine's state: wheel patterns packed one bit per cam,
+/// positions, model and limitation history. Two machines with equal states
+/// produce the same key from here on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MachineState {
+    /// Cam `i` of wheel `w` (in machine order) is bit `i` of `cams[w]`
+    cams: [u64; 12],
+    positions: [u8; 12],
+    start: [u8; 12],
+    characters: u64,
+    model: MachineModel,
+    p5_limitation: bool,
+    // χ2 one back, ψ1 one back, P5 one back, P5 two back
+    limitation: [bool; 4],
+}
+
+impl MachineState {
+    pub fn patterns(&self) -> WheelPatterns {
+        let mut patterns = WheelPatterns::new_zeroed();
+
+        for (wheel, &cams) in self.cams.iter().enumerate() {
+            for (i, cam) in patterns.wheel_mut(wheel).iter_mut().enumerate() {
+                *cam = cams >> i & 1 == 1;
+            }
+        }
+
+        patterns
+    }
+
+    /// The wheel positions at the time of the snapshot.
+    pub fn positions(&self) -> MessageSettings {
+        MessageSettings::from_array(self.positions)
+    }
+
+    pub fn start_settings(&self) -> MessageSettings {
+        MessageSettings::from_array(self.start)
+    }
+
+    pub fn character(&self) -> u64 {
+        self.characters
+    }
+
+    pub fn model(&self) -> MachineModel {
+        self.model
+    }
+}
+
+/// Writes the model, counters and limitation history on their own lines,
+/// followed by one line per wheel in the key file layout with the current
+/// position in place of the start position.
+impl fmt::Display for MachineState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = |cam: bool| if cam { "x" } else { "." };
+
+        writeln!(f, "model {}", self.model)?;
+        writeln!(f, "p5 {}", if self.p5_limitation { "on" } else { "off" })?;
+        writeln!(f, "character {}", self.characters)?;
+        write!(f, "limitation ")?;
+        for &back in &self.limitation {
+            f.write_str(mark(back))?;
+        }
+        writeln!(f)?;
+        write!(f, "start")?;
+        for position in self.start {
+            write!(f, " {position}")?;
+        }
+        writeln!(f)?;
+
+        for (wheel, name) in MessageSettings::WHEEL_NAMES.iter().enumerate() {
+            let length = MessageSettings::WHEEL_LENGTHS[wheel];
+
+            write!(f, "{name} {length} ")?;
+            for i in 0..length {
+                f.write_str(mark(self.cams[wheel] >> i & 1 == 1))?;
+            }
+            writeln!(f, " {}", self.positions[wheel])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl LorenzMachine {
+    pub fn snapshot(&self) -> MachineState {
+        let patterns = self.patterns();
+        let mut cams = [0; 12];
+
+        for (wheel, packed) in cams.iter_mut().enumerate() {
+            for (i, &cam) in patterns.wheel(wheel).iter().enumerate() {
+                *packed |= (cam as u64) << i;
+            }
+        }
+
+        MachineState {
+            cams,
+            positions: self.message_settings().to_array(),
+            start: self.start.to_array(),
+            characters: self.characters,
+            model: self.model,
+            p5_limitation: self.p5_limitation,
+            limitation: [self.mu.chi_2_back, self.mu.psi_1_back, self.mu.p5_back[0], self.mu.p5_back[1]],
+        }
+    }
+
+    pub fn from_state(state: &MachineState) -> Self {
+        let mut machine = LorenzMachine::new(&state.patterns());
+        machine.restore(state);
+        machine
+    }
+
+    /// Puts the machine back exactly as it was when `state` was taken,
+    /// patterns included.
+    pub fn restore(&mut self, state: &MachineState) {
+        let patterns = state.patterns();
+        let [chi_2_back, psi_1_back, p5_one_back, p5_two_back] = state.limitation;
+
+        *self = LorenzMachine::new(&patterns);
+        self.model = state.model;
+        self.p5_limitation = state.p5_limitation;
+        self.start = state.start_settings();
+        self.characters = state.characters;
+
+        let positions = state.positions();
+        self.psi.set_positions(positions.psi);
+        self.mu.set_positions(positions.mu);
+        self.chi.set_positions(positions.chi);
+        self.mu.chi_2_back = chi_2_back;
+        self.mu.psi_1_back = psi_1_back;
+        self.mu.p5_back = [p5_one_back, p5_two_back];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn machine() -> LorenzMachine {
+        let mut machine = LorenzMachine::new_random(&mut StdRng::seed_from_u64(57))
+            .with_model(MachineModel::SZ42B)
+            .with_p5_limitation(true);
+        machine.set_message_settings(&MessageSettings::from_array([7, 0, 12, 9, 33, 5, 18, 2, 30, 11, 0, 4])).unwrap();
+        machine
+    }
+
+    #[test]
+    fn restore_resumes_the_same_key() {
+        let mut machine = machine();
+        machine.process((0..100).map(|i| i % 32)).for_each(drop);
+
+        let state = machine.snapshot();
+        let mut restored = LorenzMachine::new_zeroed();
+        restored.restore(&state);
+
+        assert!(restored == machine);
+        assert_eq!(restored.snapshot(), state);
+        assert!(machine.process(0..32).eq(restored.process(0..32)));
+    }
+
+    #[test]
+    fn snapshots_differ_once_machines_diverge() {
+        let mut a = machine();
+        let b = LorenzMachine::from_state(&a.snapshot());
+        assert_eq!(a.snapshot(), b.snapshot());
+
+        a.step_machine_with_plaintext(0x10);
+
+        assert_ne!(a.snapshot(), b.snapshot());
+        assert_eq!(a.snapshot().patterns(), b.snapshot().patterns());
+    }
+
+    #[test]
+    fn prints_a_stable_form() {
+        let mut machine = LorenzMachine::new_zeroed().with_model(MachineModel::SZ42A);
+        machine.step_machine();
+
+        assert_eq!(machine.snapshot().to_string(), EXPECTED);
+
+        const EXPECTED: &str = "\
+model SZ42A
+p5 off
+character 1
+limitation ....
+start 0 0 0 0 0 0 0 0 0 0 0 0
+psi1 43 ........................................... 1
+psi2 47 ............................................... 1
+psi3 51 ................................................... 1
+psi4 53 ..................................................... 1
+psi5 59 ........................................................... 1
+mu37 37 ..................................... 0
+mu61 61 ............................................................. 1
+chi1 41 ......................................... 1
+chi2 31 ............................... 1
+chi3 29 ............................. 1
+chi4 26 .......................... 1
+chi5 23 ....................... 1
+";
+    }
+}
@@ -0,0 +1,227 @@
+//! Just enough of the WAV format to write the modem's audio and read back a
+//! PCM recording of it.
+
+use core::fmt;
+
+/// The length of the header `header` writes.
+pub const HEADER_LEN: usize = 44;
+
+/// The header for `samples` 16-bit mono samples at `sample_rate`, to be
+/// followed by the samples in little-endian order. A WAV file cannot hold
+/// more than 4 GiB.
+pub fn header(sample_rate: u32, samples: u32) -> Result<[u8; HEADER_LEN], WavError> {
+    let data_len = samples.checked_mul(2).filter(|len| *len <= u32::MAX - 36).ok_or(WavError::TooLong { samples })?;
+    let byte_rate = sample_rate.checked_mul(2).ok_or(WavError::SampleRateTooHigh { sample_rate })?;
+    let mut header = [0; HEADER_LEN];
+    let fields: [&[u8]; 12] = [
+        b"RIFF",
+        &(36 + data_len).to_le_bytes(),
+        b"WAVEfmt ",
+        &16u32.to_le_bytes(),
+        &1u16.to_le_bytes(),
+        &1u16.to_le_bytes(),
+        &sample_rate.to_le_bytes(),
+        &byte_rate.to_le_bytes(),
+        &2u16.to_le_bytes(),
+        &16u16.to_le_bytes(),
+        b"data",
+        &data_len.to_le_bytes(),
+    ];
+
+    let mut at = 0;
+    for field in fields {
+        header[at..at + field.len()].copy_from_slice(field);
+        at += field.len();
+    }
+
+    Ok(header)
+}
+
+/// Why a recording could not be read or written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavError {
+    /// The file does not start with a RIFF WAVE header.
+    NotWave,
+    /// The samples are compressed or floating point rather than PCM.
+    NotPcm { format: u16 },
+    /// The samples are neither 8 nor 16 bits.
+    UnsupportedBits { bits: u16 },
+    /// There is no `fmt ` or `data` chunk.
+    MissingChunk { id: &'static str },
+    /// The header gives no samples per second.
+    ZeroSampleRate,
+    /// The samples would not fit in a WAV file's 32-bit lengths.
+    TooLong { samples: u32 },
+    /// The bytes a second would not fit in the header.
+    SampleRateTooHigh { sample_rate: u32 },
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WavError::NotWave => write!(f, "not a WAV file"),
+            WavError::NotPcm { format } => write!(f, "the samples are in format {format}, not PCM"),
+            WavError::UnsupportedBits { bits } => write!(f, "{bits}-bit samples are not supported"),
+            WavError::MissingChunk { id } => write!(f, "there is no {id:?} chunk"),
+            WavError::ZeroSampleRate => write!(f, "the sample rate is 0"),
+            WavError::TooLong { samples } => write!(f, "{samples} samples are too many for a WAV file"),
+            WavError::SampleRateTooHigh { sample_rate } =>
+                write!(f, "a sample rate of {sample_rate} is too high for a WAV file"),
+        }
+    }
+}
+
+/// A PCM recording, borrowed from the bytes of the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wav<'a> {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Bits per sample, 8 or 16
+    pub bits: u16,
+    data: &'a [u8],
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+impl<'a> Wav<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, WavError> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(WavError::NotWave);
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut rest = &bytes[12..];
+        while rest.len() >= 8 {
+            let id = &rest[0..4];
+            // Recordings cut short, or streamed with the length left unknown,
+            // keep whatever is there
+            let len = (u32_at(rest, 4) as usize).min(rest.len() - 8);
+            let body = &rest[8..8 + len];
+
+            match id {
+                b"fmt " if len >= 16 => format = Some(body),
+                b"data" => data = Some(body),
+                _ => {}
+            }
+
+            // Chunks are padded to an even length
+            rest = &rest[(8 + len + len % 2).min(rest.len())..];
+        }
+
+        let format = format.ok_or(WavError::MissingChunk { id: "fmt " })?;
+        let data = data.ok_or(WavError::MissingChunk { id: "data" })?;
+
+        match u16_at(format, 0) {
+            1 => {}
+            format => return Err(WavError::NotPcm { format }),
+        }
+        let bits = u16_at(format, 14);
+        if bits != 8 && bits != 16 {
+            return Err(WavError::UnsupportedBits { bits });
+        }
+        let sample_rate = u32_at(format, 4);
+        if sample_rate == 0 {
+            return Err(WavError::ZeroSampleRate);
+        }
+
+        Ok(Wav {
+            sample_rate,
+            channels: u16_at(format, 2).max(1),
+            bits,
+            data,
+        })
+    }
+
+    /// The samples of the first channel, as 16-bit values.
+    pub fn samples(&self) -> Samples<'a> {
+        let frame = self.channels as usize * self.bits as usize / 8;
+        Samples { frames: self.data.chunks_exact(frame), bits: self.bits }
+    }
+}
+
+pub struct Samples<'a> {
+    frames: core::slice::ChunksExact<'a, u8>,
+    bits: u16,
+}
+
+impl Iterator for Samples<'_> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frames.next()?;
+
+        // 8-bit samples are unsigned around 128
+        Some(match self.bits {
+            8 => (frame[0] as i16 - 128) << 8,
+            _ => i16::from_le_bytes([frame[0], frame[1]]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let samples = [0i16, 1000, -1000, i16::MAX, i16::MIN];
+        let mut file = [0u8; HEADER_LEN + 10];
+        file[..HEADER_LEN].copy_from_slice(&header(8000, 5).unwrap());
+        for (i, sample) in samples.iter().enumerate() {
+            file[HEADER_LEN + 2 * i..][..2].copy_from_slice(&sample.to_le_bytes());
+        }
+
+        let wav = Wav::parse(&file).unwrap();
+        assert_eq!((wav.sample_rate, wav.channels, wav.bits), (8000, 1, 16));
+        assert!(wav.samples().eq(samples));
+    }
+
+    #[test]
+    fn takes_the_first_channel_of_other_layouts() {
+        // 8-bit stereo, with a chunk to skip over before the samples
+        let mut file = [0u8; 58];
+        file[..HEADER_LEN].copy_from_slice(&header(11025, 0).unwrap());
+        file[22] = 2;
+        file[34] = 8;
+        file[36..40].copy_from_slice(b"LIST");
+        file[40..44].copy_from_slice(&1u32.to_le_bytes());
+        file[46..50].copy_from_slice(b"data");
+        file[50..54].copy_from_slice(&4u32.to_le_bytes());
+        file[54..58].copy_from_slice(&[255, 0, 0, 255]);
+
+        let wav = Wav::parse(&file).unwrap();
+        assert_eq!((wav.sample_rate, wav.channels, wav.bits), (11025, 2, 8));
+        assert!(wav.samples().eq([127 << 8, -128 << 8]));
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_read() {
+        let mut file = header(8000, 0).unwrap();
+        assert_eq!(Wav::parse(&file[..40]), Err(WavError::MissingChunk { id: "data" }));
+        assert_eq!(Wav::parse(b"RIFX"), Err(WavError::NotWave));
+
+        file[20] = 3;
+        assert_eq!(Wav::parse(&file), Err(WavError::NotPcm { format: 3 }));
+        file[20] = 1;
+        file[34] = 24;
+        assert_eq!(Wav::parse(&file), Err(WavError::UnsupportedBits { bits: 24 }));
+        file[34] = 16;
+        file[24..28].fill(0);
+        assert_eq!(Wav::parse(&file), Err(WavError::ZeroSampleRate));
+    }
+    #[test]
+    fn refuses_headers_that_would_overflow() {
+        let most = (u32::MAX - 36) / 2;
+
+        assert!(header(8000, most).is_ok());
+        assert_eq!(header(8000, most + 1), Err(WavError::TooLong { samples: most + 1 }));
+        assert_eq!(header(u32::MAX, 0), Err(WavError::SampleRateTooHigh { sample_rate: u32::MAX }));
+    }
+}
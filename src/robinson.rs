@@ -0,0 +1,248 @@
+//! Heath Robinson, the two-tape machine that came before Colossus.
+//!
+//! The cipher was punched on one loop of tape and the chi wheels on another,
+//! the second as long as the product of the wheels' lengths, 41 × 31 for the
+//! 1+2 break-in. Blank tape made the cipher loop's length coprime to the chi
+//! loop's, so the two fell one against the other by a different amount on
+//! every revolution of the cipher, and one revolution counted one setting.
+//!
+//! The tapes were driven by friction, and stretched, so the chi tape could
+//! slip a character behind the cipher. The count for the rest of that
+//! revolution is then at the wrong setting, and every later count is printed
+//! against a setting it was not made at. Colossus made the chi patterns
+//! electronically, leaving one tape to run on its sprocket holes.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::colossus::{ChiTape, Score, Test};
+use crate::lorenz::{MessageSettings, WheelPatterns};
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// The cipher and chi tapes, and what the machine counts.
+#[derive(Clone, Copy, Debug)]
+pub struct Robinson<'a> {
+    cipher: &'a [u8],
+    patterns: &'a WheelPatterns,
+    wheels: [bool; 5],
+    test: Test,
+    slip_rate: f32,
+    seed: u64,
+}
+
+/// The count from one revolution of the cipher loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Revolution {
+    /// The setting the count is printed against, and the count
+    pub score: Score,
+    /// The times the chi tape slipped during the revolution
+    pub slips: usize,
+}
+
+impl<'a> Robinson<'a> {
+    /// Counts `test` with the chi wheels in `wheels` punched on the chi tape.
+    pub fn new(cipher: &'a [u8], patterns: &'a WheelPatterns, wheels: [bool; 5], test: Test) -> Self {
+        Robinson { cipher, patterns, wheels, test, slip_rate: 0.0, seed: 0 }
+    }
+
+    /// Lets the chi tape slip a character behind on average once in every
+    /// `1 / slip_rate` characters read, at places drawn from `seed`.
+    pub fn with_slips(mut self, slip_rate: f32, seed: u64) -> Self {
+        self.slip_rate = slip_rate;
+        self.seed = seed;
+        self
+    }
+
+    /// The length of the chi loop, the product of its wheels' lengths.
+    pub fn chi_loop(&self) -> usize {
+        (0..5).filter(|&i| self.wheels[i]).map(|i| MessageSettings::WHEEL_LENGTHS[7 + i]).product()
+    }
+
+    /// The length of the cipher loop, with the blank tape spliced in to make
+    /// it coprime to the chi loop.
+    pub fn cipher_loop(&self) -> usize {
+        let chi_loop = self.chi_loop();
+        (self.cipher.len().max(1)..).find(|&len| gcd(len, chi_loop) == 1).expect("some length is coprime")
+    }
+
+    /// The chi wheel settings at a position on the chi loop.
+    fn settings(&self, position: usize) -> [Option<u8>; 5] {
+        core::array::from_fn(|i| {
+            let length = MessageSettings::WHEEL_LENGTHS[7 + i];
+            self.wheels[i].then_some((position % length) as u8)
+        })
+    }
+
+    /// One count per revolution, until every setting has been tried once.
+    pub fn run(&self) -> Revolutions<'a> {
+        Revolutions {
+            robinson: *self,
+            rng: StdRng::seed_from_u64(self.seed),
+            revolution: 0,
+            drift: 0,
+            until_slip: None,
+        }
+    }
+}
+
+pub struct Revolutions<'a> {
+    robinson: Robinson<'a>,
+    rng: StdRng,
+    revolution: usize,
+    /// How far the chi tape has slipped behind, as a position on its loop
+    drift: usize,
+    /// The characters to be read before the next slip, if the tape slips
+    until_slip: Option<usize>,
+}
+
+impl Revolutions<'_> {
+    fn draw_slip(rng: &mut StdRng, slip_rate: f32) -> Option<usize> {
+        // Evenly between none and twice the mean gap between slips
+        let mean = 1.0 / slip_rate;
+        (slip_rate > 0.0).then(|| rng.gen_range(0..=(2.0 * mean) as usize))
+    }
+}
+
+impl Iterator for Revolutions<'_> {
+    type Item = Revolution;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let robinson = &self.robinson;
+        let chi_loop = robinson.chi_loop();
+        if self.revolution == chi_loop {
+            return None;
+        }
+
+        let printed = self.revolution * robinson.cipher_loop() % chi_loop;
+        let actual = (printed + chi_loop - self.drift) % chi_loop;
+        self.revolution += 1;
+
+        // A slip holds the chi tape back, reading the same character again,
+        // or before the first read the character under the head
+        let mut tape = ChiTape::new(robinson.patterns, robinson.settings(actual)).peekable();
+        let mut last = None;
+        let mut slips = 0;
+        let rng = &mut self.rng;
+        let until_slip = &mut self.until_slip;
+        if until_slip.is_none() {
+            *until_slip = Self::draw_slip(rng, robinson.slip_rate);
+        }
+        let chi = core::iter::from_fn(|| {
+            match until_slip {
+                Some(0) => {
+                    slips += 1;
+                    *until_slip = Self::draw_slip(rng, robinson.slip_rate);
+                    last = last.or_else(|| tape.peek().copied());
+                }
+                _ => {
+                    if let Some(until_slip) = until_slip {
+                        *until_slip -= 1;
+                    }
+                    last = tape.next();
+                }
+            }
+            last
+        });
+
+        let count = robinson.test.count(robinson.cipher.iter().copied(), chi);
+        self.drift = (self.drift + slips) % chi_loop;
+
+        Some(Revolution { score: Score { chi: robinson.settings(printed), count }, slips })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colossus::Condition;
+    use crate::colossus::tests::patterns;
+
+    const ONE_AND_TWO: [bool; 5] = [true, true, false, false, false];
+
+    /// A cipher of the chi wheels alone, from `settings`.
+    fn cipher(patterns: &WheelPatterns, settings: [Option<u8>; 5]) -> [u8; 400] {
+        let mut chi = ChiTape::new(patterns, settings);
+        core::array::from_fn(|_| chi.next().unwrap())
+    }
+
+    fn test() -> Test {
+        Test::new(&[Condition { mask: 0b00011, cross: false }])
+    }
+
+    #[test]
+    fn pads_the_cipher_loop_to_a_coprime_length() {
+        let patterns = patterns();
+        let cipher = [0; 41 * 3];
+        let robinson = Robinson::new(&cipher, &patterns, ONE_AND_TWO, test());
+
+        assert_eq!(robinson.chi_loop(), 1271);
+        // 123 is 3 × 41 and 124 is 4 × 31
+        assert_eq!(robinson.cipher_loop(), 125);
+        assert_eq!(Robinson::new(&cipher[..100], &patterns, ONE_AND_TWO, test()).cipher_loop(), 100);
+    }
+
+    #[test]
+    fn tries_every_setting_once() {
+        let patterns = patterns();
+        let cipher = cipher(&patterns, [Some(30), Some(12), None, None, None]);
+        let robinson = Robinson::new(&cipher, &patterns, ONE_AND_TWO, test());
+
+        let mut seen = [[false; 31]; 41];
+        let mut best = None::<Score>;
+        for revolution in robinson.run() {
+            let [Some(chi_1), Some(chi_2), ..] = revolution.score.chi else { panic!("both wheels are set") };
+            assert!(!seen[chi_1 as usize][chi_2 as usize]);
+            seen[chi_1 as usize][chi_2 as usize] = true;
+
+            if best.filter(|best| best.count >= revolution.score.count).is_none() {
+                best = Some(revolution.score);
+            }
+        }
+
+        assert!(seen.iter().flatten().all(|&seen| seen));
+        assert_eq!(best, Some(Score { chi: [Some(30), Some(12), None, None, None], count: 399 }));
+    }
+
+    #[test]
+    fn slips_spoil_the_counts() {
+        let patterns = patterns();
+        let cipher = cipher(&patterns, [Some(30), Some(12), None, None, None]);
+        let robinson = Robinson::new(&cipher, &patterns, ONE_AND_TWO, test()).with_slips(0.002, 7);
+
+        let mut slips = 0;
+        let mut right = None;
+        for revolution in robinson.run() {
+            slips += revolution.slips;
+            if revolution.score.chi[..2] == [Some(30), Some(12)] {
+                right = Some(revolution.score.count);
+            }
+        }
+
+        assert!(slips > 0);
+        assert!(right.unwrap() < 399);
+    }
+    #[test]
+    fn a_slip_before_the_first_read_repeats_the_first_character() {
+        // A first character which reads differently from a blank in 1+2
+        let mut patterns = patterns();
+        patterns.chi_1[0] = true;
+        let settings = [Some(30), Some(12), None, None, None];
+        let cipher = cipher(&patterns, settings);
+        let rate = 0.001;
+        let seed = (0..)
+            .find(|&seed| Revolutions::draw_slip(&mut StdRng::seed_from_u64(seed), rate) == Some(0))
+            .unwrap();
+        let robinson = Robinson::new(&cipher, &patterns, ONE_AND_TWO, test()).with_slips(rate, seed);
+
+        let first = robinson.run().next().unwrap();
+        assert_eq!(first.slips, 1);
+
+        let mut chi = ChiTape::new(&patterns, robinson.settings(0));
+        let first_character = ChiTape::new(&patterns, robinson.settings(0)).next();
+        let slipped = first_character.into_iter().chain(core::iter::from_fn(|| chi.next()));
+        assert_eq!(first.score.count, test().count(cipher.iter().copied(), slipped));
+    }
+}
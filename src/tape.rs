@@ -0,0 +1,350 @@
+//! Five-hole punched paper tape drawn as text, one row per code, and read
+//! back from the same drawing.
+//!
+//! ```text
+//! |oo .  |
+//! |  o.oo|
+//! ```
+
+use core::fmt;
+use core::str::Lines;
+
+/// How a tape is drawn: the impulse each hole carries from left to right,
+/// where the sprocket hole falls, and the characters used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TapeStyle {
+    /// The impulse, 1 to 5, of each hole from left to right
+    order: [u8; 5],
+    /// The number of holes left of the sprocket hole
+    sprocket_after: usize,
+    hole: char,
+    blank: char,
+    sprocket: char,
+    edge: char,
+}
+
+/// Why a row of tape could not be read. Lines are numbered from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TapeError {
+    /// The row does not start and end with the edge of the tape.
+    MissingEdge { line: usize },
+    /// The row is not five holes and a sprocket hole wide.
+    WrongWidth { line: usize, found: usize },
+    /// The sprocket hole is not where the style puts it.
+    MissingSprocket { line: usize },
+    /// A hole is neither punched nor blank. Columns count from 0 inside the edges.
+    InvalidHole { line: usize, column: usize, found: char },
+}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TapeError::MissingEdge { line } => write!(f, "line {line}: the row has no edges"),
+            TapeError::WrongWidth { line, found } =>
+                write!(f, "line {line}: expected five holes and a sprocket, found {found} columns"),
+            TapeError::MissingSprocket { line } => write!(f, "line {line}: the sprocket hole is missing"),
+            TapeError::InvalidHole { line, column, found } =>
+                write!(f, "line {line}: column {column} is {found:?}, not a hole or blank"),
+        }
+    }
+}
+
+/// Why a hole order was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderError {
+    /// The order does not name each impulse 1 to 5 exactly once.
+    NotAPermutation { order: [u8; 5] },
+    /// There are only five holes to put left of the sprocket.
+    SprocketOutOfRange { sprocket_after: usize },
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            OrderError::NotAPermutation { order } =>
+                write!(f, "{order:?} does not name impulses 1 to 5 once each"),
+            OrderError::SprocketOutOfRange { sprocket_after } =>
+                write!(f, "{sprocket_after} holes left of the sprocket, but there are only 5"),
+        }
+    }
+}
+
+/// Why a set of characters cannot draw a tape that reads back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlyphError {
+    /// Two of the hole, blank, sprocket and edge are the same character.
+    Repeated { glyph: char },
+    /// Only a blank may be whitespace, which is trimmed from the end of a row.
+    Whitespace { glyph: char },
+}
+
+impl fmt::Display for GlyphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GlyphError::Repeated { glyph } => write!(f, "{glyph:?} is used for two different things"),
+            GlyphError::Whitespace { glyph } => write!(f, "{glyph:?} is whitespace, which only a blank may be"),
+        }
+    }
+}
+
+impl Default for TapeStyle {
+    fn default() -> Self {
+        TapeStyle::ASCII
+    }
+}
+
+impl TapeStyle {
+    /// `|ooo.o |`, with impulses 1 to 3 left of the sprocket.
+    pub const ASCII: TapeStyle = TapeStyle {
+        order: [1, 2, 3, 4, 5],
+        sprocket_after: 3,
+        hole: 'o',
+        blank: ' ',
+        sprocket: '.',
+        edge: '|',
+    };
+
+    /// `│●●●·● │`, with impulses 1 to 3 left of the sprocket.
+    pub const UNICODE: TapeStyle = TapeStyle {
+        order: [1, 2, 3, 4, 5],
+        sprocket_after: 3,
+        hole: '●',
+        blank: ' ',
+        sprocket: '·',
+        edge: '│',
+    };
+
+    /// Puts the holes in `order`, impulses 1 to 5 from left to right, with
+    /// `sprocket_after` of them left of the sprocket hole.
+    pub fn with_order(mut self, order: [u8; 5], sprocket_after: usize) -> Result<Self, OrderError> {
+        let mut seen = [false; 5];
+        for &impulse in &order {
+            match seen.get_mut(usize::from(impulse).wrapping_sub(1)) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(OrderError::NotAPermutation { order }),
+            }
+        }
+        if sprocket_after > 5 {
+            return Err(OrderError::SprocketOutOfRange { sprocket_after });
+        }
+
+        self.order = order;
+        self.sprocket_after = sprocket_after;
+        Ok(self)
+    }
+
+    /// Draws punched holes with `hole`, unpunched ones with `blank`, and the
+    /// sprocket hole and the edges of the tape with `sprocket` and `edge`.
+    pub fn with_glyphs(mut self, hole: char, blank: char, sprocket: char, edge: char) -> Result<Self, GlyphError> {
+        let glyphs = [hole, blank, sprocket, edge];
+        for (i, &glyph) in glyphs.iter().enumerate() {
+            if glyphs[..i].contains(&glyph) {
+                return Err(GlyphError::Repeated { glyph });
+            }
+            if glyph != blank && glyph.is_whitespace() {
+                return Err(GlyphError::Whitespace { glyph });
+            }
+        }
+
+        self.hole = hole;
+        self.blank = blank;
+        self.sprocket = sprocket;
+        self.edge = edge;
+        Ok(self)
+    }
+
+    /// The impulse of each hole from left to right.
+    pub fn order(&self) -> [u8; 5] {
+        self.order
+    }
+
+    /// The number of holes left of the sprocket hole.
+    pub fn sprocket_after(&self) -> usize {
+        self.sprocket_after
+    }
+
+    /// The column inside the edges of each hole from left to right.
+    fn columns(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.order.iter().enumerate().map(|(i, &impulse)| {
+            let column = if i < self.sprocket_after { i } else { i + 1 };
+            (column, impulse)
+        })
+    }
+
+    /// One row of tape for `code`.
+    pub fn row(&self, code: u8) -> Row<'_> {
+        Row { style: self, code }
+    }
+
+    /// Draws `codes`, one row per line.
+    pub fn draw<'a>(&'a self, codes: &'a [u8]) -> Tape<'a> {
+        Tape { style: self, codes }
+    }
+
+    /// Reads the code from a single row.
+    pub fn parse_row(&self, row: &str, line: usize) -> Result<u8, TapeError> {
+        let inner = row.strip_prefix(self.edge)
+            .and_then(|r| r.strip_suffix(self.edge))
+            .ok_or(TapeError::MissingEdge { line })?;
+
+        let found = inner.chars().count();
+        if found != 6 {
+            return Err(TapeError::WrongWidth { line, found });
+        }
+        if inner.chars().nth(self.sprocket_after) != Some(self.sprocket) {
+            return Err(TapeError::MissingSprocket { line });
+        }
+
+        let mut code = 0;
+        for (column, impulse) in self.columns() {
+            match inner.chars().nth(column) {
+                Some(c) if c == self.hole => code |= 1 << (impulse - 1),
+                Some(c) if c == self.blank => {}
+                found => return Err(TapeError::InvalidHole { line, column, found: found.unwrap_or(' ') }),
+            }
+        }
+
+        Ok(code)
+    }
+
+    /// Reads a drawn tape back into codes, skipping blank lines.
+    pub fn parse<'a>(&'a self, text: &'a str) -> TapeReader<'a> {
+        TapeReader { style: self, lines: text.lines(), line: 0 }
+    }
+}
+
+pub struct Row<'a> {
+    style: &'a TapeStyle,
+    code: u8,
+}
+
+impl fmt::Display for Row<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut inner = [self.style.sprocket; 6];
+        for (column, impulse) in self.style.columns() {
+            let punched = self.code >> (impulse - 1) & 1 == 1;
+            inner[column] = if punched { self.style.hole } else { self.style.blank };
+        }
+
+        fmt::Write::write_char(f, self.style.edge)?;
+        for c in inner {
+            fmt::Write::write_char(f, c)?;
+        }
+        fmt::Write::write_char(f, self.style.edge)
+    }
+}
+
+pub struct Tape<'a> {
+    style: &'a TapeStyle,
+    codes: &'a [u8],
+}
+
+impl fmt::Display for Tape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &code in self.codes {
+            writeln!(f, "{}", self.style.row(code))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct TapeReader<'a> {
+    style: &'a TapeStyle,
+    lines: Lines<'a>,
+    line: usize,
+}
+
+impl Iterator for TapeReader<'_> {
+    type Item = Result<u8, TapeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let text = self.lines.next()?;
+            self.line += 1;
+
+            // Drop a trailing carriage return, but not a row's blank holes
+            let text = text.trim_end_matches(|c: char| c.is_whitespace() && c != self.style.blank);
+            if !text.is_empty() {
+                return Some(self.style.parse_row(text, self.line));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::{String, ToString};
+
+    use super::*;
+
+    fn drawn(style: &TapeStyle, codes: &[u8]) -> String {
+        style.draw(codes).to_string()
+    }
+
+    #[test]
+    fn draws_impulses_either_side_of_the_sprocket() {
+        assert_eq!(drawn(&TapeStyle::ASCII, &[0x03, 0x1C]), "|oo .  |\n|  o.oo|\n");
+        assert_eq!(drawn(&TapeStyle::UNICODE, &[0x10]), "│   · ●│\n");
+    }
+
+    #[test]
+    fn follows_the_hole_order() {
+        let style = TapeStyle::ASCII.with_order([1, 2, 3, 4, 5], 2).unwrap();
+        assert_eq!(drawn(&style, &[0x04, 0x08]), "|  .o  |\n|  . o |\n");
+
+        let reversed = TapeStyle::ASCII.with_order([5, 4, 3, 2, 1], 3).unwrap();
+        assert_eq!(drawn(&reversed, &[0x01]), "|   . o|\n");
+    }
+
+    #[test]
+    fn rejects_orders_that_are_not_a_permutation() {
+        let style = TapeStyle::ASCII;
+
+        for order in [[0, 1, 2, 3, 4], [1, 2, 3, 4, 9], [1, 2, 2, 4, 5]] {
+            assert_eq!(style.with_order(order, 2), Err(OrderError::NotAPermutation { order }));
+        }
+        assert_eq!(style.with_order([1, 2, 3, 4, 5], 6), Err(OrderError::SprocketOutOfRange { sprocket_after: 6 }));
+        assert_eq!(style.with_order([1, 2, 3, 4, 5], 5).map(|style| style.sprocket_after()), Ok(5));
+    }
+
+    #[test]
+    fn rejects_glyphs_that_would_misread() {
+        let style = TapeStyle::ASCII;
+
+        assert_eq!(style.with_glyphs('o', 'o', '.', '|'), Err(GlyphError::Repeated { glyph: 'o' }));
+        assert_eq!(style.with_glyphs('o', ' ', ' ', '|'), Err(GlyphError::Repeated { glyph: ' ' }));
+        assert_eq!(style.with_glyphs('o', ' ', 'o', '|'), Err(GlyphError::Repeated { glyph: 'o' }));
+        assert_eq!(style.with_glyphs('o', '-', '.', ' '), Err(GlyphError::Whitespace { glyph: ' ' }));
+        assert_eq!(style.with_glyphs('\t', ' ', '.', '|'), Err(GlyphError::Whitespace { glyph: '\t' }));
+    }
+
+    #[test]
+    fn reads_back_every_code() {
+        let codes: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let styles = [
+            TapeStyle::ASCII,
+            TapeStyle::UNICODE,
+            TapeStyle::ASCII.with_order([3, 1, 5, 2, 4], 1).unwrap(),
+            TapeStyle::ASCII.with_glyphs('#', '-', '*', '!').unwrap(),
+        ];
+
+        for style in styles {
+            let tape = drawn(&style, &codes);
+            assert!(style.parse(&tape).map(Result::unwrap).eq(codes), "{style:?}");
+        }
+    }
+
+    #[test]
+    fn reports_malformed_rows() {
+        let style = TapeStyle::ASCII;
+        let text = "|oo .  |\n\n|oo.   |\n|oo . |\n|ox .  |\noo .  \n";
+        let mut codes = style.parse(text);
+
+        assert_eq!(codes.next(), Some(Ok(0x03)));
+        assert_eq!(codes.next(), Some(Err(TapeError::MissingSprocket { line: 3 })));
+        assert_eq!(codes.next(), Some(Err(TapeError::WrongWidth { line: 4, found: 5 })));
+        assert_eq!(codes.next(), Some(Err(TapeError::InvalidHole { line: 5, column: 1, found: 'x' })));
+        assert_eq!(codes.next(), Some(Err(TapeError::MissingEdge { line: 6 })));
+        assert_eq!(codes.next(), None);
+    }
+}
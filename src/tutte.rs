@@ -0,0 +1,514 @@
+//! Tutte's break of the machine from a depth, two messages sent on the same
+//! start positions.
+//!
+//! The cipher texts Z1 and Z2 share their key K, so Z1 + Z2 = P1 + P2 and the
+//! key drops out. A crib in one message reads a piece of the other, and once
+//! either plain text is read out the key is Z + P. Tiltman read about 4000
+//! characters of key this way from the depth of 30 August 1941.
+//!
+//! K = χ + ψ', and the chi wheels step every character while ψ' often stands
+//! still, so ΔK leans towards Δχ. Written out in rows as wide as a chi wheel,
+//! each impulse of ΔK has that wheel's Δχ running down its columns, which is
+//! how Tutte found χ1 at 41. Taking the recovered χ off the key leaves ψ', and
+//! where ψ' stands still the motor did not move.
+
+use core::fmt;
+
+use crate::delta::{Delta, Impulse};
+use crate::lorenz::{Key, MessageSettings, WheelPatterns};
+
+/// The shortest key `Tutte::recover` works from. With less, a column of χ1
+/// can be outvoted by the psi wheels.
+pub const MIN_KEY_LENGTH: usize = 2500;
+
+/// How many times each chi wheel is voted on again once all five have a
+/// first reading.
+const CHI_REVOTES: usize = 3;
+
+/// The most times, before every psi cam has been seen, the psi wheels can
+/// move onto the same character without the key showing it.
+const MAX_HIDDEN_STEPS: usize = 8;
+
+/// Two cipher texts in depth.
+#[derive(Clone, Copy, Debug)]
+pub struct Depth<'a> {
+    first: &'a [u8],
+    second: &'a [u8],
+}
+
+impl<'a> Depth<'a> {
+    pub fn new(first: &'a [u8], second: &'a [u8]) -> Self {
+        Depth { first, second }
+    }
+
+    /// The characters both messages cover.
+    pub fn len(&self) -> usize {
+        self.first.len().min(self.second.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Z1 + Z2, which is also P1 + P2.
+    pub fn combined(&self) -> impl Iterator<Item = u8> + 'a {
+        self.first.iter().zip(self.second).map(|(a, b)| a ^ b)
+    }
+
+    /// What the other message reads with `crib` at `at` in one of them.
+    pub fn read<'b>(&self, at: usize, crib: &'b [u8]) -> impl Iterator<Item = u8> + 'b
+    where
+        'a: 'b,
+    {
+        self.combined().skip(at).zip(crib).map(|(combined, plain)| combined ^ plain)
+    }
+
+    /// The key under `plain` read from the first message at `at`. Swap the
+    /// messages for plain text read from the second.
+    pub fn key<'b>(&self, at: usize, plain: &'b [u8]) -> impl Iterator<Item = u8> + 'b
+    where
+        'a: 'b,
+    {
+        self.first.iter().skip(at).zip(plain).map(|(cipher, plain)| cipher ^ plain)
+    }
+
+    /// Drags `crib` through the depth, giving each position the crib fits at
+    /// with the number of characters of the other message's reading that are
+    /// `likely`.
+    pub fn drag<'b, F>(&self, crib: &'b [u8], likely: F) -> impl Iterator<Item = (usize, usize)> + 'b
+    where
+        'a: 'b,
+        F: Fn(u8) -> bool + Copy + 'b,
+    {
+        let depth = *self;
+        let positions = (self.len() + 1).saturating_sub(crib.len());
+        (0..positions).map(move |at| (at, depth.read(at, crib).filter(|&c| likely(c)).count()))
+    }
+}
+
+/// Why no key could be recovered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TutteError {
+    /// The key is shorter than `MIN_KEY_LENGTH`.
+    TooShort { len: usize },
+    /// No psi patterns give the key with the chi recovered, so a chi wheel or
+    /// the reading of the depth is wrong.
+    NoPsiPattern,
+    /// The SZ40's motor wheels cannot move the psi wheels as the key shows.
+    /// The character is where μ37 was found to disagree with itself.
+    NoMotorPattern { at: usize },
+}
+
+impl fmt::Display for TutteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TutteError::TooShort { len } => write!(f, "{len} characters of key, at least {MIN_KEY_LENGTH} are needed"),
+            TutteError::NoPsiPattern => write!(f, "no psi patterns fit the key less the chi wheels"),
+            TutteError::NoMotorPattern { at } => write!(f, "at {at}: no SZ40 motor pattern fits the key"),
+        }
+    }
+}
+
+/// The psi cams seen so far, impulse by impulse, as bits of a word.
+#[derive(Clone, Copy, Debug, Default)]
+struct Cams {
+    known: [u64; 5],
+    crosses: [u64; 5],
+}
+
+impl Cams {
+    /// Sets the cams the psi wheels show after `steps` steps to `psi`,
+    /// failing if one has already been seen the other way.
+    fn set(&mut self, steps: usize, psi: u8) -> bool {
+        for i in 0..5 {
+            let cam = 1 << (steps % MessageSettings::WHEEL_LENGTHS[i]);
+            let cross = psi >> i & 1 == 1;
+
+            if self.known[i] & cam != 0 {
+                if (self.crosses[i] & cam != 0) != cross {
+                    return false;
+                }
+            } else {
+                self.known[i] |= cam;
+                self.crosses[i] |= if cross { cam } else { 0 };
+            }
+        }
+        true
+    }
+
+    fn write(&self, patterns: &mut WheelPatterns) {
+        for i in 0..5 {
+            for (position, cam) in patterns.wheel_mut(i).iter_mut().enumerate() {
+                *cam = self.crosses[i] >> position & 1 == 1;
+            }
+        }
+    }
+}
+
+/// A stretch of ψ' standing on one character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Run {
+    start: usize,
+    len: usize,
+    /// The positions of the psi wheels the stretch covers, 0 if the patterns
+    /// cannot give it
+    steps: usize,
+}
+
+/// The runs of ψ', with the psi wheels moved on through each.
+struct Runs<'a> {
+    tutte: Tutte<'a>,
+    patterns: &'a WheelPatterns,
+    at: usize,
+    steps: usize,
+}
+
+impl Iterator for Runs<'_> {
+    type Item = Run;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tutte = self.tutte;
+        let (start, len, psi) = tutte.run(self.patterns, self.at)?;
+
+        // The run ends where the psi wheels move onto another character
+        let last = start + len == tutte.key.len();
+        let steps = (0..=len)
+            .take_while(|&step| psi_at(self.patterns, self.steps + step) == psi)
+            .count();
+        let steps = if steps == 0 || (!last && steps > len) { 0 } else { steps };
+
+        self.at += len;
+        self.steps += steps;
+        Some(Run { start, len, steps })
+    }
+}
+
+/// What the chi wheels show `at` characters in, with every wheel started at 0.
+fn chi_at(patterns: &WheelPatterns, at: usize) -> u8 {
+    (0..5).fold(0, |chi, i| {
+        let wheel = patterns.wheel(7 + i);
+        chi | (wheel[at % wheel.len()] as u8) << i
+    })
+}
+
+/// What the psi wheels show after `steps` steps from 0.
+fn psi_at(patterns: &WheelPatterns, steps: usize) -> u8 {
+    (0..5).fold(0, |psi, i| {
+        let wheel = patterns.wheel(i);
+        psi | (wheel[steps % wheel.len()] as u8) << i
+    })
+}
+
+/// A stretch of key to recover the wheel patterns from.
+#[derive(Clone, Copy, Debug)]
+pub struct Tutte<'a> {
+    key: &'a [u8],
+}
+
+impl<'a> Tutte<'a> {
+    pub fn new(key: &'a [u8]) -> Self {
+        Tutte { key }
+    }
+
+    /// How many times ΔK in `impulse` agrees with itself `width` characters
+    /// on. This is highest at the length of the chi wheel on that impulse.
+    pub fn period(&self, impulse: u8, width: usize) -> usize {
+        let delta = || Delta::new(Impulse::new(self.key.iter().copied(), impulse));
+        delta().zip(delta().skip(width)).filter(|(a, b)| a == b).count()
+    }
+
+    /// Sets the chi patterns from the key, each started where the message
+    /// starts. A wheel and the psi wheel on the same impulse can both be
+    /// turned over without changing the key; this takes the one starting on
+    /// a dot.
+    fn chi(&self, patterns: &mut WheelPatterns) {
+        // Every character votes at first. After that only those vote where
+        // the other four impulses show ΔK = Δχ, as they do together where the
+        // psi wheels stood still.
+        let mut delta = [[false; 41]; 5];
+        for i in 0..5 {
+            delta[i] = self.vote(&delta, i, 0);
+        }
+        for _ in 0..CHI_REVOTES {
+            for i in 0..5 {
+                delta[i] = self.vote(&delta, i, 0b11111 & !(1 << i));
+            }
+        }
+
+        for (i, delta) in delta.iter().enumerate() {
+            let wheel = patterns.wheel_mut(7 + i);
+            let mut cam = false;
+            for (position, cam_delta) in wheel.iter_mut().zip(delta) {
+                *position = cam;
+                cam ^= cam_delta;
+            }
+        }
+    }
+
+    /// Δχ on impulse `i` from the columns of ΔK, counting only characters
+    /// where the impulses in `others` agree with the Δχ already in `delta`.
+    fn vote(&self, delta: &[[bool; 41]; 5], i: usize, others: u8) -> [bool; 41] {
+        let length = MessageSettings::WHEEL_LENGTHS[7 + i];
+        let delta_chi = |at: usize| (0..5).fold(0, |chi, j| {
+            chi | (delta[j][at % MessageSettings::WHEEL_LENGTHS[7 + j]] as u8) << j
+        });
+
+        let mut votes = [0isize; 41];
+        for (at, key) in Delta::new(self.key.iter().copied()).enumerate() {
+            if (key ^ delta_chi(at)) & others == 0 {
+                votes[at % length] += if key >> i & 1 == 1 { 1 } else { -1 };
+            }
+        }
+        let mut voted: [bool; 41] = core::array::from_fn(|cam| cam < length && votes[cam] > 0);
+
+        // The crosses of a Δχ go round the wheel in pairs, so an odd one out
+        // is the column voted on most narrowly
+        if voted.iter().filter(|&&cross| cross).count() % 2 == 1 {
+            let narrowest = (0..length).min_by_key(|&cam| votes[cam].abs()).expect("wheels have cams");
+            voted[narrowest] = !voted[narrowest];
+        }
+
+        voted
+    }
+
+    /// The stretch of ψ' from `at`: where it starts, how long it stands and
+    /// what it stands on.
+    fn run(&self, patterns: &WheelPatterns, at: usize) -> Option<(usize, usize, u8)> {
+        if at >= self.key.len() {
+            return None;
+        }
+        let psi = |at: usize| self.key[at] ^ chi_at(patterns, at);
+        let first = psi(at);
+        let len = (at..self.key.len()).take_while(|&i| psi(i) == first).count();
+
+        Some((at, len, first))
+    }
+
+    fn runs<'b>(&self, patterns: &'b WheelPatterns) -> Runs<'b>
+    where
+        'a: 'b,
+    {
+        Runs { tutte: *self, patterns, at: 0, steps: 0 }
+    }
+
+    /// Goes on through the runs from `at`, with the psi wheels moved on
+    /// `steps`, trying each hidden step that `hidden` allows before every cam
+    /// has been seen.
+    fn search(&self, chi: &WheelPatterns, at: usize, steps: usize, cams: Cams, hidden: usize) -> Option<Cams> {
+        if steps >= MessageSettings::WHEEL_LENGTHS[4] {
+            // Every cam is known, so the rest of the key must follow
+            let mut patterns = *chi;
+            cams.write(&mut patterns);
+            return self.runs(&patterns).all(|run| run.steps > 0).then_some(cams);
+        }
+
+        let (_, len, psi) = self.run(chi, at)?;
+        for run_steps in 1..=len.min(1 + hidden) {
+            let mut next = cams;
+            if (steps..steps + run_steps).all(|step| next.set(step, psi)) {
+                let found = self.search(chi, at + len, steps + run_steps, next, hidden + 1 - run_steps);
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Sets the psi patterns from the key less the chi patterns already in
+    /// `patterns`. The key cannot show where among like characters the
+    /// message started, which `motor` puts right.
+    ///
+    /// Where ψ' changes the psi wheels moved, but they can also move onto the
+    /// character they stood on. Every psi wheel is periodic, so this takes the
+    /// fewest such hidden steps that make all five patterns repeat.
+    fn psi(&self, patterns: &mut WheelPatterns) -> Result<(), TutteError> {
+        let cams = (0..=MAX_HIDDEN_STEPS)
+            .find_map(|hidden| self.search(patterns, 0, 0, Cams::default(), hidden))
+            .ok_or(TutteError::NoPsiPattern)?;
+
+        cams.write(patterns);
+        Ok(())
+    }
+
+    /// Whether the psi wheels moved after each character, where the key
+    /// shows it, from the chi and psi patterns in `patterns`.
+    ///
+    /// Within a run the psi wheels moved one time fewer than the positions it
+    /// covers, which only says when if that is never or every time. The first
+    /// run can start anywhere among like psi characters and the last can end
+    /// anywhere, so how the wheels moved within those is not known.
+    fn motion<'b>(&self, patterns: &'b WheelPatterns) -> impl Iterator<Item = Option<bool>> + 'b
+    where
+        'a: 'b,
+    {
+        let len = self.key.len();
+        self.runs(patterns).flat_map(move |run| {
+            let ends = run.start == 0 || run.start + run.len == len;
+            (1..=run.len).map(move |i| match i == run.len {
+                // Moving onto the next run
+                true => (run.start + run.len < len).then_some(true),
+                false if ends => None,
+                false if run.steps == 1 => Some(false),
+                false if run.steps == run.len => Some(true),
+                false => None,
+            })
+        })
+    }
+
+    /// Sets the SZ40's motor patterns from the movement of the psi wheels,
+    /// with the chi and psi patterns already in `patterns`.
+    ///
+    /// μ37 moves on only where μ61 shows a cross, so wherever the motor
+    /// changes from one character to the next μ61 has a cross in that column.
+    /// Counting those crosses gives μ37's position at every character. The
+    /// psi patterns are then turned to start where the motor has them.
+    fn motor(&self, patterns: &mut WheelPatterns) -> Result<(), TutteError> {
+        let read = *patterns;
+        let mut mu_61 = [false; 61];
+        for (at, (motor, next)) in self.motion(&read).zip(self.motion(&read).skip(1)).enumerate() {
+            if let (Some(motor), Some(next)) = (motor, next) {
+                mu_61[at % 61] |= motor != next;
+            }
+        }
+
+        // With 37 crosses on μ61, μ37 comes back to the same cam every 61
+        // characters and a cross between two like cams never shows. Any 37
+        // crosses including those seen then give the same motor.
+        let mu_37 = self.mu_37(&read, &mu_61).or_else(|error| {
+            let mut missing = 37usize.saturating_sub(mu_61.iter().filter(|&&cross| cross).count());
+            for cam in mu_61.iter_mut().filter(|cam| !**cam) {
+                if missing > 0 {
+                    *cam = true;
+                    missing -= 1;
+                }
+            }
+            self.mu_37(&read, &mu_61).map_err(|_| error)
+        })?;
+
+        patterns.mu_61 = mu_61;
+        patterns.mu_37 = mu_37;
+
+        // The psi patterns were started anywhere among the like characters
+        // of the first run, so turn them to where the motor says
+        let first = self.runs(&read).next().expect("recover checked the key is long enough");
+        let mut position = 0;
+        let moves = (0..first.len - 1)
+            .filter(|&at| {
+                let motor = mu_37[position % 37];
+                position += mu_61[at % 61] as usize;
+                motor
+            })
+            .count();
+        for i in 0..5 {
+            let wheel = patterns.wheel_mut(i);
+            let turn = (first.steps + wheel.len() - 1 - moves % wheel.len()) % wheel.len();
+            wheel.rotate_left(turn);
+        }
+        Ok(())
+    }
+
+    /// μ37 as the motor reads it, stepped on by `mu_61`.
+    fn mu_37(&self, patterns: &WheelPatterns, mu_61: &[bool; 61]) -> Result<[bool; 37], TutteError> {
+        let mut mu_37: [Option<bool>; 37] = [None; 37];
+        let mut position = 0;
+        for (at, motor) in self.motion(patterns).enumerate() {
+            if let Some(motor) = motor {
+                match mu_37[position % 37] {
+                    Some(cam) if cam != motor => return Err(TutteError::NoMotorPattern { at }),
+                    _ => mu_37[position % 37] = Some(motor),
+                }
+            }
+            position += mu_61[at % 61] as usize;
+        }
+
+        Ok(mu_37.map(|cam| cam.unwrap_or(false)))
+    }
+
+    /// Recovers the whole key of an SZ40 from the key stream: the patterns
+    /// started where the message starts, so every start position is 0.
+    pub fn recover(&self) -> Result<Key, TutteError> {
+        if self.key.len() < MIN_KEY_LENGTH {
+            return Err(TutteError::TooShort { len: self.key.len() });
+        }
+
+        let mut patterns = WheelPatterns::new_zeroed();
+        self.chi(&mut patterns);
+        self.psi(&mut patterns)?;
+        self.motor(&mut patterns)?;
+
+        Ok(Key { patterns, settings: MessageSettings::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::LorenzMachine;
+
+    const LEN: usize = 4000;
+
+    fn machine() -> LorenzMachine {
+        let mut machine = LorenzMachine::new_random(&mut StdRng::seed_from_u64(30));
+        machine.set_message_settings(&MessageSettings::from_array([3, 17, 40, 8, 51, 30, 2, 12, 30, 5, 19, 22])).unwrap();
+        machine
+    }
+
+    fn key(machine: &LorenzMachine) -> [u8; LEN] {
+        let mut machine = machine.clone();
+        let mut key = machine.key_codes();
+        core::array::from_fn(|_| key.next().unwrap())
+    }
+
+    #[test]
+    fn reads_one_message_against_the_other() {
+        let key = [0b10101, 0b00011, 0b11111];
+        let (first, second) = ([1, 2, 3], [4, 5, 6]);
+        let cipher = |plain: [u8; 3]| core::array::from_fn::<u8, 3, _>(|i| plain[i] ^ key[i]);
+        let (z1, z2) = (cipher(first), cipher(second));
+        let depth = Depth::new(&z1, &z2);
+
+        assert!(depth.read(1, &first[1..]).eq(second[1..].iter().copied()));
+        assert!(depth.key(0, &first).eq(key));
+        assert!(depth.drag(&first[..2], |c| c == 4 || c == 5).eq([(0, 2), (1, 0)]));
+    }
+
+    #[test]
+    fn finds_the_length_of_chi_1() {
+        let key = key(&machine());
+        let tutte = Tutte::new(&key);
+
+        let best = (30..50).max_by_key(|&width| tutte.period(1, width));
+        assert_eq!(best, Some(41));
+    }
+
+    #[test]
+    fn recovers_the_chi_wheels() {
+        let machine = machine();
+        let key = key(&machine);
+        let mut patterns = WheelPatterns::new_zeroed();
+        Tutte::new(&key).chi(&mut patterns);
+
+        let actual = machine.patterns();
+        let settings = machine.start_settings().chi;
+        for i in 0..5 {
+            let (wheel, found) = (actual.wheel(7 + i), patterns.wheel(7 + i));
+            let flip = wheel[settings[i] as usize];
+            assert!((0..wheel.len()).all(|cam| found[cam] ^ flip == wheel[(settings[i] as usize + cam) % wheel.len()]));
+        }
+    }
+
+    #[test]
+    fn recovers_a_key_giving_the_same_key_stream() {
+        let key = key(&machine());
+        let recovered = Tutte::new(&key).recover().unwrap();
+
+        let mut machine = LorenzMachine::from_key(&recovered).unwrap();
+        assert!(machine.key_codes().take(LEN).eq(key));
+        assert_eq!(Tutte::new(&key[..2499]).recover(), Err(TutteError::TooShort { len: 2499 }));
+    }
+}
@@ -0,0 +1,82 @@
+//! Breaks messages made with `LorenzMachine` the way Bletchley Park did.
+
+use lorenz::colossus::{Colossus, Program, Score, Stage};
+use lorenz::ita2::Encoder;
+use lorenz::robinson::Robinson;
+use lorenz::tutte::{Depth, Tutte};
+use lorenz::{LorenzMachine, MachineModel, MessageSettings};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const TEXT: &str = "\
+FROM  OKW  TO  ALL  COMMANDS..  ATTACK  WILL  COMMENCE  AT  0500  HOURS  TOMORROW..  \
+ALL  UNITS  REPORT  READINESS  BY  2300..  SUPPLY  OFFICERS  CONFIRM  STOCKS  OF  \
+FUEL  AND  AMMUNITION..  MESSAGE  ENDS..\r\n\r\n";
+
+const REPLY: &str = "\
+TO  OKW  FROM  ARMY  GROUP  SOUTH..  ALL  UNITS  READY..  FUEL  STOCKS  STAND  AT  \
+HALF..  REQUEST  RAIL  TRANSPORT  FOR  THE  SECOND  ECHELON..  ENDS..\r\n\r\n";
+
+fn cipher(model: MachineModel, settings: [u8; 12], len: usize) -> (LorenzMachine, Vec<u8>) {
+    let mut machine = LorenzMachine::new_random(&mut StdRng::seed_from_u64(1942)).with_model(model);
+    machine.set_message_settings(&MessageSettings::from_array(settings)).unwrap();
+
+    let plain = Encoder::new(TEXT.chars().cycle()).codes().take(len);
+    let cipher = machine.clone().process(plain).collect();
+
+    (machine, cipher)
+}
+
+#[test]
+fn colossus_sets_the_chi_wheels() {
+    let settings = [5, 40, 12, 0, 33, 20, 7, 27, 14, 3, 25, 19];
+    let (machine, cipher) = cipher(MachineModel::SZ40, settings, 5000);
+    let patterns = machine.patterns();
+    let colossus = Colossus::new(&cipher, &patterns);
+
+    let program = Program::parse("1+2=. total=2700; 3=.; 4=.; 5=.").unwrap();
+    let found = colossus.settle(&program).map(Option::unwrap);
+
+    assert_eq!(found, machine.start_settings().chi);
+}
+
+
+#[test]
+fn heath_robinson_agrees_with_colossus_until_its_tapes_slip() {
+    let settings = [5, 40, 12, 0, 33, 20, 7, 27, 14, 3, 25, 19];
+    let (machine, cipher) = cipher(MachineModel::SZ40, settings, 3000);
+    let patterns = machine.patterns();
+    let stage = Stage::parse("1+2=.").unwrap();
+    let robinson = Robinson::new(&cipher, &patterns, [true, true, false, false, false], stage.test);
+
+    let best = |revolutions: &mut dyn Iterator<Item = Score>| revolutions.max_by_key(|score| score.count).unwrap().chi;
+    let colossus = Colossus::new(&cipher, &patterns).best(&stage, [None; 5]).chi;
+
+    assert_eq!(best(&mut robinson.run().map(|r| r.score)), colossus);
+    assert_eq!(colossus[..2], [Some(27), Some(14)]);
+    assert_ne!(best(&mut robinson.with_slips(0.0005, 1).run().map(|r| r.score)), colossus);
+}
+
+#[test]
+fn tutte_recovers_the_key_from_a_depth() {
+    let settings = [40, 2, 17, 33, 9, 11, 58, 3, 22, 14, 7, 19];
+    let (machine, first) = cipher(MachineModel::SZ40, settings, 4000);
+    let first_plain: Vec<u8> = Encoder::new(TEXT.chars().cycle()).codes().take(4000).collect();
+    let second_plain: Vec<u8> = Encoder::new(REPLY.chars().cycle()).codes().take(4000).collect();
+    let second: Vec<u8> = machine.clone().process(second_plain.iter().copied()).collect();
+    let depth = Depth::new(&first, &second);
+
+    // The crib reads the most likely letters of the reply where it fits
+    let crib = &first_plain[100..160];
+    let likely: Vec<u8> = Encoder::new("ETAOINSRH ".chars()).codes().collect();
+    let (at, _) = depth.drag(crib, |c| likely.contains(&c)).max_by_key(|&(_, count)| count).unwrap();
+    assert!(first_plain[at..].starts_with(crib));
+    assert!(depth.read(at, crib).eq(second_plain[at..at + crib.len()].iter().copied()));
+
+    let key: Vec<u8> = depth.key(0, &first_plain).collect();
+    let recovered = Tutte::new(&key).recover().unwrap();
+    let read: Vec<u8> = LorenzMachine::from_key(&recovered).unwrap().process(second).collect();
+
+    assert_eq!(Tutte::new(&key).period(1, 41), (36..48).map(|width| Tutte::new(&key).period(1, width)).max().unwrap());
+    assert_eq!(read, second_plain);
+}
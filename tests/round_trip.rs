@@ -0,0 +1,77 @@
+//! Enciphers and deciphers text through the public API, as the firmware and
+//! host tools use it.
+
+use lorenz::ita2::{Decoder, Encoder, Event};
+use lorenz::lorenz::Key;
+use lorenz::modem::{self, Modem, Wav};
+use lorenz::{LorenzMachine, MachineModel, MessageSettings};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+const TEXT: &str = "HELLO, WORLD! 1944 ATTACK AT DAWN.";
+
+fn machines(model: MachineModel, p5_limitation: bool) -> (LorenzMachine, LorenzMachine) {
+    let mut machine = LorenzMachine::new_random(&mut StdRng::seed_from_u64(57))
+        .with_model(model)
+        .with_p5_limitation(p5_limitation);
+    machine.set_message_settings(&MessageSettings::from_array([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12])).unwrap();
+
+    (machine.clone(), machine)
+}
+
+#[test]
+fn every_model_deciphers_its_own_cipher_text() {
+    let models = [
+        (MachineModel::SZ40, false),
+        (MachineModel::SZ42A, false),
+        (MachineModel::SZ42B, false),
+        (MachineModel::SZ42B, true),
+        (MachineModel::Demo, false),
+    ];
+
+    for (model, p5_limitation) in models {
+        let (mut sender, mut receiver) = machines(model, p5_limitation);
+
+        let cipher_text: Vec<u8> = sender.process(Encoder::new(TEXT.chars()).codes()).collect();
+        let plain_text: String = Decoder::new(receiver.decipher(cipher_text.iter().copied())).filter_map(Event::as_char).collect();
+
+        assert_eq!(plain_text, TEXT, "{model}");
+        assert_eq!(sender.character(), receiver.character());
+    }
+}
+
+#[test]
+fn a_key_file_carries_the_whole_setting() {
+    let (mut sender, _) = machines(MachineModel::SZ40, false);
+    let key: Key = sender.key().to_string().parse().unwrap();
+    let mut receiver = LorenzMachine::from_key(&key).unwrap();
+
+    let cipher_text: Vec<u8> = sender.process(Encoder::new(TEXT.chars()).codes()).collect();
+    let plain_text: String = Decoder::new(receiver.process(cipher_text.iter().copied())).filter_map(Event::as_char).collect();
+
+    assert_eq!(plain_text, TEXT);
+}
+
+#[test]
+fn cipher_text_depends_on_the_settings() {
+    let (mut a, mut b) = machines(MachineModel::SZ40, false);
+    b.set_message_settings(&MessageSettings::default()).unwrap();
+
+    assert!(!a.process(Encoder::new(TEXT.chars()).codes()).eq(b.process(Encoder::new(TEXT.chars()).codes())));
+}
+
+#[test]
+fn cipher_text_survives_the_radio_link() {
+    let (mut sender, mut receiver) = machines(MachineModel::SZ42A, false);
+    let modem = Modem::WIDE;
+
+    let audio: Vec<i16> = modem.modulate(sender.process(Encoder::new(TEXT.chars()).codes())).collect();
+    let mut file = modem::header(modem.sample_rate(), u32::try_from(audio.len()).unwrap()).unwrap().to_vec();
+    file.extend(audio.iter().flat_map(|sample| sample.to_le_bytes()));
+
+    let recording = Wav::parse(&file).unwrap();
+    let cipher_text: Vec<u8> = modem.receive(recording.samples()).map(Result::unwrap).collect();
+    let plain_text: String = Decoder::new(receiver.decipher(cipher_text)).filter_map(Event::as_char).collect();
+
+    assert_eq!(plain_text, TEXT);
+}